@@ -0,0 +1,337 @@
+// This file is part of Grust, GObject introspection bindings for Rust
+//
+// Copyright (C) 2015  Mikhail Zabaluev <mikhail.zabaluev@gmail.com>
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; either
+// version 2.1 of the License, or (at your option) any later version.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+
+extern crate proc_macro;
+extern crate proc_macro2;
+extern crate quote;
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::{Attribute, Data, DeriveInput, Fields, Ident, Lit, LitInt, LitStr,
+          Meta, NestedMeta};
+
+#[proc_macro_derive(IntrospectedEnum, attributes(nick))]
+pub fn derive_introspected_enum(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    expand_or_error(introspected_enum(&input))
+}
+
+#[proc_macro_derive(IntrospectedFlags, attributes(flags))]
+pub fn derive_introspected_flags(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    expand_or_error(introspected_flags(&input))
+}
+
+#[proc_macro_derive(ErrorDomain, attributes(domain))]
+pub fn derive_error_domain(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    expand_or_error(error_domain(&input))
+}
+
+fn expand_or_error(res: syn::Result<TokenStream2>) -> TokenStream {
+    match res {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into()
+    }
+}
+
+struct EnumMember {
+    ident: Ident,
+    name: String,
+    nick: String
+}
+
+fn enum_members(input: &DeriveInput) -> syn::Result<Vec<EnumMember>> {
+    let data = match input.data {
+        Data::Enum(ref data) => data,
+        _ => {
+            return Err(syn::Error::new_spanned(input,
+                    "IntrospectedEnum can only be derived for enums"));
+        }
+    };
+    let mut members = Vec::new();
+    for variant in data.variants.iter() {
+        match variant.fields {
+            Fields::Unit => {}
+            _ => {
+                return Err(syn::Error::new_spanned(variant,
+                        "enum variants must not have fields"));
+            }
+        }
+        let nick = match string_attr(&variant.attrs, "nick")? {
+            Some(nick) => nick,
+            None => kebab_case(&variant.ident.to_string())
+        };
+        let name = format!("{}_{}",
+                           screaming_snake_case(&input.ident.to_string()),
+                           screaming_snake_case(&variant.ident.to_string()));
+        members.push(EnumMember {
+            ident: variant.ident.clone(),
+            name: name,
+            nick: nick
+        });
+    }
+    Ok(members)
+}
+
+fn introspected_enum(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let members = enum_members(input)?;
+    let idents: Vec<_> = members.iter().map(|m| &m.ident).collect();
+    let names: Vec<_> = members.iter().map(|m| &m.name).collect();
+    let nicks: Vec<_> = members.iter().map(|m| &m.nick).collect();
+    let (impl_generics, ty_generics, where_clause)
+        = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::grust::enumeration::IntrospectedEnum
+            for #name #ty_generics #where_clause
+        {
+            fn from_int(v: ::grust::types::gint)
+                        -> ::std::result::Result<Self,
+                                ::grust::enumeration::UnknownValue>
+            {
+                #(
+                    if v == #name::#idents as ::grust::types::gint {
+                        return ::std::result::Result::Ok(#name::#idents);
+                    }
+                )*
+                ::std::result::Result::Err(
+                        ::grust::enumeration::UnknownValue(v))
+            }
+
+            fn to_int(&self) -> ::grust::types::gint {
+                match *self {
+                    #( #name::#idents => #name::#idents as ::grust::types::gint, )*
+                }
+            }
+
+            fn name(&self) -> &'static str {
+                match *self {
+                    #( #name::#idents => #nicks, )*
+                }
+            }
+        }
+
+        impl #impl_generics ::grust::enumeration::EnumValues
+            for #name #ty_generics #where_clause
+        {
+            fn values() -> &'static [::grust::enumeration::ValueDef] {
+                const VALUES: &'static [::grust::enumeration::ValueDef] = &[
+                    #(
+                        ::grust::enumeration::ValueDef {
+                            value: #name::#idents as ::grust::types::gint,
+                            name: #names,
+                            nick: #nicks
+                        },
+                    )*
+                ];
+                VALUES
+            }
+        }
+    })
+}
+
+struct FlagsMember {
+    ident: Ident,
+    value: LitInt
+}
+
+fn flags_members(input: &DeriveInput) -> syn::Result<Vec<FlagsMember>> {
+    let mut members = Vec::new();
+    for attr in input.attrs.iter() {
+        if !attr.path.is_ident("flags") {
+            continue;
+        }
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            meta => {
+                return Err(syn::Error::new_spanned(meta,
+                        "expected #[flags(NAME = value, ...)]"));
+            }
+        };
+        for nested in list.nested.iter() {
+            let member = match *nested {
+                NestedMeta::Meta(Meta::NameValue(ref nv)) => {
+                    match (nv.path.get_ident(), &nv.lit) {
+                        (Some(ident), &Lit::Int(ref value)) => {
+                            FlagsMember {
+                                ident: ident.clone(),
+                                value: value.clone()
+                            }
+                        }
+                        _ => {
+                            return Err(syn::Error::new_spanned(nv,
+                                    "expected NAME = integer value"));
+                        }
+                    }
+                }
+                _ => {
+                    return Err(syn::Error::new_spanned(nested,
+                            "expected NAME = integer value"));
+                }
+            };
+            members.push(member);
+        }
+    }
+    Ok(members)
+}
+
+fn check_flags_struct(input: &DeriveInput) -> syn::Result<()> {
+    if let Data::Struct(ref data) = input.data {
+        if let Fields::Unnamed(ref fields) = data.fields {
+            if fields.unnamed.len() == 1 {
+                return Ok(());
+            }
+        }
+    }
+    Err(syn::Error::new_spanned(input,
+            "IntrospectedFlags can only be derived for a struct \
+             wrapping a single guint value"))
+}
+
+fn introspected_flags(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    check_flags_struct(input)?;
+    let name = &input.ident;
+    let members = flags_members(input)?;
+    let idents: Vec<_> = members.iter().map(|m| &m.ident).collect();
+    let values: Vec<_> = members.iter().map(|m| &m.value).collect();
+    let type_prefix = screaming_snake_case(&name.to_string());
+    let names: Vec<_> = idents.iter().map(|ident| {
+        format!("{}_{}", type_prefix, ident)
+    }).collect();
+    let nicks: Vec<_> = idents.iter().map(|ident| {
+        ident.to_string().to_lowercase().replace('_', "-")
+    }).collect();
+
+    Ok(quote! {
+        impl #name {
+            #(
+                pub const #idents: #name = #name(#values);
+            )*
+        }
+
+        impl ::grust::flags::IntrospectedFlags for #name {
+            fn from_uint(v: ::grust::types::guint)
+                         -> ::std::result::Result<Self,
+                                 ::grust::flags::UnknownFlags>
+            {
+                const KNOWN_MASK: ::grust::types::guint = 0 #(| #values)*;
+                if v & !KNOWN_MASK == 0 {
+                    ::std::result::Result::Ok(#name(v))
+                } else {
+                    ::std::result::Result::Err(
+                            ::grust::flags::UnknownFlags::new(v, KNOWN_MASK))
+                }
+            }
+
+            fn to_uint(&self) -> ::grust::types::guint {
+                self.0
+            }
+        }
+
+        // The same operators as provided by the macro for hand-written
+        // flags types, so that both behave alike
+        ::grust::g_impl_flags_ops!(#name);
+
+        impl ::grust::flags::FlagsValues for #name {
+            fn values() -> &'static [::grust::flags::ValueDef] {
+                const VALUES: &'static [::grust::flags::ValueDef] = &[
+                    #(
+                        ::grust::flags::ValueDef {
+                            value: #values,
+                            name: #names,
+                            nick: #nicks
+                        },
+                    )*
+                ];
+                VALUES
+            }
+        }
+    })
+}
+
+fn error_domain(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let domain = match string_attr(&input.attrs, "domain")? {
+        Some(domain) => domain,
+        None => {
+            return Err(syn::Error::new_spanned(input,
+                    "missing #[domain = \"...\"] attribute"));
+        }
+    };
+    if domain.contains('\0') {
+        return Err(syn::Error::new_spanned(input,
+                "error domain name must not contain NUL characters"));
+    }
+    let lit = LitStr::new(&format!("{}\0", domain), Span::call_site());
+
+    Ok(quote! {
+        impl ::grust::error::Domain for #name {
+            fn domain() -> ::grust::quark::Quark {
+                static QUARK: ::grust::quark::StaticQuark =
+                    ::grust::quark::StaticQuark::new(#lit);
+                QUARK.get()
+            }
+        }
+    })
+}
+
+fn string_attr(attrs: &[Attribute], name: &str) -> syn::Result<Option<String>> {
+    for attr in attrs.iter() {
+        if !attr.path.is_ident(name) {
+            continue;
+        }
+        match attr.parse_meta()? {
+            Meta::NameValue(ref nv) => {
+                if let Lit::Str(ref s) = nv.lit {
+                    return Ok(Some(s.value()));
+                }
+            }
+            _ => {}
+        }
+        return Err(syn::Error::new_spanned(attr,
+                format!("expected #[{} = \"...\"]", name)));
+    }
+    Ok(None)
+}
+
+// Converts a CamelCase identifier to the kebab-case form
+// used for GLib value nicks.
+fn kebab_case(ident: &str) -> String {
+    let mut nick = String::with_capacity(ident.len() + 4);
+    for (i, c) in ident.chars().enumerate() {
+        if c.is_uppercase() {
+            if i != 0 {
+                nick.push('-');
+            }
+            nick.extend(c.to_lowercase());
+        } else if c == '_' {
+            nick.push('-');
+        } else {
+            nick.push(c);
+        }
+    }
+    nick
+}
+
+fn screaming_snake_case(ident: &str) -> String {
+    kebab_case(ident).to_uppercase().replace('-', "_")
+}
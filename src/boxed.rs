@@ -17,6 +17,7 @@
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 
 use gtype::GType;
+use panic;
 use types::gpointer;
 use util::{box_free, box_from_pointer, box_into_pointer};
 
@@ -40,7 +41,9 @@ unsafe extern "C" fn box_copy<T>(raw: gpointer) -> gpointer
     where T: Clone
 {
     let boxed: Box<T> = box_from_pointer(raw);
-    let copy: Box<T> = boxed.clone();
+    // There is no way to report a failed copy to the caller,
+    // so a panic in clone() is fatal regardless of the policy
+    let copy: Box<T> = panic::catch_or_abort(|| boxed.clone());
     // Prevent the original value from being dropped
     mem::forget(boxed);
     box_into_pointer(copy)
@@ -0,0 +1,195 @@
+// This file is part of Grust, GObject introspection bindings for Rust
+//
+// Copyright (C) 2015  Mikhail Zabaluev <mikhail.zabaluev@gmail.com>
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; either
+// version 2.1 of the License, or (at your option) any later version.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+
+use quark::Quark;
+use types::gpointer;
+use util::into_destroy_notify;
+use util::{tagged_box_free, tagged_box_into_pointer, tagged_box_is};
+use util::{tagged_box_ref, tagged_box_take};
+
+use glib as ffi;
+
+use std::any::Any;
+use std::cell::UnsafeCell;
+use std::marker::PhantomData;
+use std::mem;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::ptr;
+
+// A keyed data list, with the same layout as a GData pointer
+// so that lists embedded in C structures can be accessed in place.
+// Lookups take a bit lock in the pointer, so it is written to
+// even when the list is only read.
+#[repr(C)]
+pub struct DataList {
+    raw: UnsafeCell<*mut ffi::GData>
+}
+
+impl DataList {
+
+    pub fn new() -> DataList {
+        DataList { raw: UnsafeCell::new(ptr::null_mut()) }
+    }
+
+    // The list must be initialized, and only contain values
+    // inserted through this API.
+    pub unsafe fn from_raw_mut<'a>(datalist: *mut *mut ffi::GData)
+                                  -> &'a mut DataList
+    {
+        &mut *(datalist as *mut DataList)
+    }
+
+    fn as_mut_raw(&self) -> *mut *mut ffi::GData {
+        self.raw.get()
+    }
+
+    // Replaces any value previously set for the key, dropping it
+    pub fn insert<T>(&mut self, key: Quark, value: T) where T: 'static {
+        unsafe {
+            ffi::g_datalist_id_set_data_full(self.as_mut_raw(),
+                    key.to_raw(),
+                    tagged_box_into_pointer(value),
+                    Some(into_destroy_notify(tagged_box_free::<T>)));
+        }
+    }
+
+    // Returns None if no value is set for the key
+    // or it has a different type
+    pub fn get<T>(&self, key: Quark) -> Option<&T> where T: 'static {
+        unsafe {
+            let p = ffi::g_datalist_id_get_data(self.as_mut_raw(),
+                                                key.to_raw());
+            tagged_box_ref(p)
+        }
+    }
+
+    pub fn contains_key(&self, key: Quark) -> bool {
+        unsafe {
+            let p = ffi::g_datalist_id_get_data(self.as_mut_raw(),
+                                                key.to_raw());
+            !p.is_null()
+        }
+    }
+
+    pub fn remove(&mut self, key: Quark) {
+        unsafe {
+            ffi::g_datalist_id_remove_data(self.as_mut_raw(), key.to_raw());
+        }
+    }
+
+    // Takes the value out of the list if it has the requested type,
+    // otherwise leaves it in place and returns None.
+    pub fn take<T>(&mut self, key: Quark) -> Option<T> where T: 'static {
+        unsafe {
+            let p = ffi::g_datalist_id_get_data(self.as_mut_raw(),
+                                                key.to_raw());
+            if p.is_null() || !tagged_box_is::<T>(p) {
+                return None;
+            }
+            ffi::g_datalist_id_remove_no_notify(self.as_mut_raw(),
+                                                key.to_raw());
+            Some(tagged_box_take(p))
+        }
+    }
+
+    // Calls the closure for each value of the requested type
+    pub fn foreach<T, F>(&self, func: F) where T: 'static, F: FnMut(Quark, &T) {
+        let mut state = ForeachState {
+            func: func,
+            panic: None,
+            marker: PhantomData
+        };
+        unsafe {
+            ffi::g_datalist_foreach(self.as_mut_raw(),
+                    Some(into_foreach_func(foreach_func::<T, F>)),
+                    &mut state as *mut ForeachState<T, F> as gpointer);
+        }
+        // The iteration is synchronous, so a panic in the closure
+        // can always be resumed here, regardless of the panic policy
+        if let Some(payload) = state.panic {
+            resume_unwind(payload);
+        }
+    }
+
+    pub fn keys(&self) -> Vec<Quark> {
+        let mut keys = Vec::new();
+        unsafe {
+            ffi::g_datalist_foreach(self.as_mut_raw(),
+                    Some(into_foreach_func(collect_key)),
+                    &mut keys as *mut Vec<Quark> as gpointer);
+        }
+        keys
+    }
+
+    pub fn clear(&mut self) {
+        unsafe { ffi::g_datalist_clear(self.as_mut_raw()); }
+    }
+}
+
+impl Default for DataList {
+    fn default() -> DataList { DataList::new() }
+}
+
+impl Drop for DataList {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+unsafe fn into_foreach_func(
+        callback: unsafe extern "C" fn(ffi::GQuark, gpointer, gpointer))
+        -> ffi::GDataForeachFunc
+{
+    mem::transmute(callback)
+}
+
+struct ForeachState<T, F> {
+    func: F,
+    panic: Option<Box<Any + Send>>,
+    marker: PhantomData<fn(&T)>
+}
+
+unsafe extern "C" fn foreach_func<T, F>(key_id: ffi::GQuark,
+                                        data: gpointer,
+                                        user_data: gpointer)
+    where T: 'static, F: FnMut(Quark, &T)
+{
+    let state = &mut *(user_data as *mut ForeachState<T, F>);
+    // The iteration cannot be stopped, so after a panic
+    // the remaining values are skipped
+    if state.panic.is_some() {
+        return;
+    }
+    if let Some(value) = tagged_box_ref::<T>(data) {
+        let func = &mut state.func;
+        let res = catch_unwind(AssertUnwindSafe(|| {
+            func(Quark::from_raw(key_id), value)
+        }));
+        if let Err(payload) = res {
+            state.panic = Some(payload);
+        }
+    }
+}
+
+unsafe extern "C" fn collect_key(key_id: ffi::GQuark,
+                                 _data: gpointer,
+                                 user_data: gpointer)
+{
+    let keys = &mut *(user_data as *mut Vec<Quark>);
+    keys.push(Quark::from_raw(key_id));
+}
@@ -16,11 +16,18 @@
 // License along with this library; if not, write to the Free Software
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 
+use gtype;
 use gtype::GType;
-use types::gint;
+use types::{gint, gpointer};
+use util::str_from_ptr;
+
+use gobject as ffi;
 
 use std::error::Error as ErrorTrait;
+use std::ffi::CString;
 use std::fmt;
+use std::ptr;
+use std::slice;
 
 pub trait IntrospectedEnum : Sized {
     fn from_int(v: gint) -> Result<Self, UnknownValue>;
@@ -32,9 +39,69 @@ pub trait EnumType : IntrospectedEnum {
     fn get_type() -> GType;
 }
 
+pub trait EnumValues : IntrospectedEnum {
+    fn values() -> &'static [ValueDef];
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct ValueDef {
+    pub value: gint,
+    pub name: &'static str,
+    pub nick: &'static str
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub struct UnknownValue(pub gint);
 
+// An enumeration value that may be unknown to the bindings,
+// such as one added in a newer version of a C library.
+// The raw value is preserved so that it can be passed back.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Lenient<E> {
+    Known(E),
+    Unknown(gint)
+}
+
+impl<E> Lenient<E> where E: IntrospectedEnum {
+
+    pub fn from_int(v: gint) -> Lenient<E> {
+        match E::from_int(v) {
+            Ok(known) => Lenient::Known(known),
+            Err(_)    => Lenient::Unknown(v)
+        }
+    }
+
+    pub fn to_int(&self) -> gint {
+        match *self {
+            Lenient::Known(ref known) => known.to_int(),
+            Lenient::Unknown(v)       => v
+        }
+    }
+}
+
+impl<E> Lenient<E> {
+
+    pub fn known(self) -> Option<E> {
+        match self {
+            Lenient::Known(known) => Some(known),
+            Lenient::Unknown(_)   => None
+        }
+    }
+
+    pub fn is_known(&self) -> bool {
+        match *self {
+            Lenient::Known(_)   => true,
+            Lenient::Unknown(_) => false
+        }
+    }
+}
+
+impl<E> From<E> for Lenient<E> where E: IntrospectedEnum {
+    fn from(val: E) -> Lenient<E> {
+        Lenient::Known(val)
+    }
+}
+
 impl ErrorTrait for UnknownValue {
     fn description(&self) -> &str {
         "unknown enumeration value"
@@ -56,3 +123,152 @@ pub fn from_int<E>(v: gint) -> Result<E, UnknownValue>
 pub fn type_of<E>() -> GType where E: EnumType {
     <E as EnumType>::get_type()
 }
+
+// The value table and its strings must live as long as the type,
+// so they are leaked.
+pub fn register_enum_type<E>(name: &str) -> GType where E: EnumValues {
+    let mut table: Vec<ffi::GEnumValue> = E::values().iter().map(|def| {
+        ffi::GEnumValue {
+            value: def.value,
+            value_name: CString::new(def.name).unwrap().into_raw(),
+            value_nick: CString::new(def.nick).unwrap().into_raw()
+        }
+    }).collect();
+    table.push(ffi::GEnumValue {
+        value: 0,
+        value_name: ptr::null(),
+        value_nick: ptr::null()
+    });
+    let table = Box::into_raw(table.into_boxed_slice());
+    let c_name = CString::new(name).unwrap();
+    let raw = unsafe {
+        ffi::g_enum_register_static(c_name.as_ptr(),
+                                    table as *const ffi::GEnumValue)
+    };
+    assert!(raw != 0, "failed to register type \"{}\"", name);
+    unsafe { GType::from_raw(raw) }
+}
+
+pub struct EnumClass {
+    raw: *mut ffi::GEnumClass
+}
+
+unsafe impl Send for EnumClass { }
+unsafe impl Sync for EnumClass { }
+
+#[repr(C)]
+pub struct EnumValue(ffi::GEnumValue);
+
+impl EnumClass {
+
+    pub fn from_type(gtype: GType) -> Option<EnumClass> {
+        unsafe {
+            // The abstract base class has no values
+            if gtype == gtype::ENUM {
+                return None;
+            }
+            if ffi::g_type_fundamental(gtype.to_raw()) != gtype::ENUM.to_raw() {
+                return None;
+            }
+            let raw = ffi::g_type_class_ref(gtype.to_raw());
+            Some(EnumClass { raw: raw as *mut ffi::GEnumClass })
+        }
+    }
+
+    pub fn of<E>() -> EnumClass where E: EnumType {
+        EnumClass::from_type(type_of::<E>())
+            .expect("EnumType::get_type() did not return an enum type")
+    }
+
+    pub fn type_of(&self) -> GType {
+        unsafe { GType::from_raw((*self.raw).g_type_class.g_type) }
+    }
+
+    pub fn minimum(&self) -> gint {
+        unsafe { (*self.raw).minimum }
+    }
+
+    pub fn maximum(&self) -> gint {
+        unsafe { (*self.raw).maximum }
+    }
+
+    pub fn values(&self) -> &[EnumValue] {
+        unsafe {
+            let raw = &*self.raw;
+            slice::from_raw_parts(raw.values as *const EnumValue,
+                                  raw.n_values as usize)
+        }
+    }
+
+    pub fn value(&self, value: gint) -> Option<&EnumValue> {
+        unsafe {
+            let p = ffi::g_enum_get_value(self.raw, value);
+            (p as *const EnumValue).as_ref()
+        }
+    }
+
+    pub fn value_by_name(&self, name: &str) -> Option<&EnumValue> {
+        let name = match CString::new(name) {
+            Ok(s) => s,
+            Err(_) => return None
+        };
+        unsafe {
+            let p = ffi::g_enum_get_value_by_name(self.raw, name.as_ptr());
+            (p as *const EnumValue).as_ref()
+        }
+    }
+
+    pub fn value_by_nick(&self, nick: &str) -> Option<&EnumValue> {
+        let nick = match CString::new(nick) {
+            Ok(s) => s,
+            Err(_) => return None
+        };
+        unsafe {
+            let p = ffi::g_enum_get_value_by_nick(self.raw, nick.as_ptr());
+            (p as *const EnumValue).as_ref()
+        }
+    }
+}
+
+impl Clone for EnumClass {
+    fn clone(&self) -> EnumClass {
+        unsafe {
+            let raw = ffi::g_type_class_ref(self.type_of().to_raw());
+            EnumClass { raw: raw as *mut ffi::GEnumClass }
+        }
+    }
+}
+
+impl Drop for EnumClass {
+    fn drop(&mut self) {
+        unsafe { ffi::g_type_class_unref(self.raw as gpointer); }
+    }
+}
+
+impl fmt::Debug for EnumClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "EnumClass({:?})", self.type_of())
+    }
+}
+
+impl EnumValue {
+
+    pub fn value(&self) -> gint {
+        self.0.value
+    }
+
+    pub fn name(&self) -> &str {
+        unsafe { str_from_ptr(self.0.value_name) }
+    }
+
+    pub fn nick(&self) -> &str {
+        unsafe { str_from_ptr(self.0.value_nick) }
+    }
+}
+
+impl fmt::Debug for EnumValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "EnumValue {{ value: {}, name: {:?}, nick: {:?} }}",
+               self.value(), self.name(), self.nick())
+    }
+}
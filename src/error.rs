@@ -20,8 +20,12 @@ use boxed::BoxedType;
 use enumeration;
 use enumeration::IntrospectedEnum;
 use gtype::GType;
+#[cfg(feature = "v2_68")]
+use panic;
 use quark::Quark;
-use types::{gint, gpointer};
+use types::{gboolean, gint, gpointer, FALSE};
+#[cfg(feature = "v2_68")]
+use types::gchar;
 use util::escape_bytestring;
 
 use glib as ffi;
@@ -29,11 +33,15 @@ use gobject;
 
 use std::any::Any;
 use std::error::Error as ErrorTrait;
-use std::ffi::CStr;
+use std::ffi::{CStr, CString};
 use std::fmt;
+use std::io;
 use std::marker::PhantomData;
 use std::mem;
+use std::ptr;
 use std::str;
+#[cfg(feature = "v2_68")]
+use std::sync::Arc;
 
 pub struct Error {
     ptr: *mut ffi::GError
@@ -44,11 +52,9 @@ pub struct DomainError<T> {
     marker: PhantomData<T>
 }
 
-#[derive(Copy, Clone, Debug)]
-pub enum Code<T> {
-    Known(T),
-    Unknown(gint)
-}
+// Error codes are decoded leniently, keeping codes unknown to
+// the bindings
+pub use enumeration::Lenient as Code;
 
 pub trait Domain : IntrospectedEnum + Any {
     fn domain() -> Quark;
@@ -58,6 +64,234 @@ pub fn domain<T>() -> Quark where T: Domain {
     <T as Domain>::domain()
 }
 
+// An error domain registered with g_error_domain_register(),
+// with every error in the domain carrying an optional Rust value
+// in its private data.
+#[cfg(feature = "v2_68")]
+pub trait ExtendedDomain : Domain {
+    type Data: Clone + Send + 'static;
+}
+
+// These are available since GLib 2.68
+#[cfg(feature = "v2_68")]
+extern "C" {
+    fn g_error_domain_register(
+            error_type_name: *const gchar,
+            error_type_private_size: usize,
+            error_type_init: unsafe extern "C" fn(*mut ffi::GError),
+            error_type_copy: unsafe extern "C" fn(*const ffi::GError,
+                                                  *mut ffi::GError),
+            error_type_clear: unsafe extern "C" fn(*mut ffi::GError))
+            -> ffi::GQuark;
+}
+
+// GLib places the private data before the GError structure,
+// padding the size to a multiple of two gsize words.
+#[cfg(feature = "v2_68")]
+fn extended_private_size<D>() -> usize {
+    let align = 2 * mem::size_of::<usize>();
+    assert!(mem::align_of::<Option<D>>() <= align,
+            "alignment of the error private data type is too large");
+    (mem::size_of::<Option<D>>() + align - 1) & !(align - 1)
+}
+
+#[cfg(feature = "v2_68")]
+unsafe fn extended_data_ptr<D>(err: *const ffi::GError) -> *mut Option<D> {
+    let offset = extended_private_size::<D>() as isize;
+    (err as *mut u8).offset(-offset) as *mut Option<D>
+}
+
+#[cfg(feature = "v2_68")]
+unsafe extern "C" fn extended_init<D>(err: *mut ffi::GError) {
+    ptr::write(extended_data_ptr::<D>(err), None);
+}
+
+#[cfg(feature = "v2_68")]
+unsafe extern "C" fn extended_copy<D>(src: *const ffi::GError,
+                                      dest: *mut ffi::GError)
+    where D: Clone
+{
+    let data = &*extended_data_ptr::<D>(src);
+    let copy = panic::catch_or_abort(|| data.clone());
+    *extended_data_ptr::<D>(dest) = copy;
+}
+
+#[cfg(feature = "v2_68")]
+unsafe extern "C" fn extended_clear<D>(err: *mut ffi::GError) {
+    let data = ptr::replace(extended_data_ptr::<D>(err), None);
+    // Errors can be freed anywhere, so a panic can't be resumed
+    panic::catch_or_abort(move || drop(data));
+}
+
+#[cfg(feature = "v2_68")]
+pub fn register_extended_domain<T>(name: &str) -> Quark
+    where T: ExtendedDomain
+{
+    let c_name = CString::new(name).unwrap();
+    let raw = unsafe {
+        g_error_domain_register(c_name.as_ptr(),
+                                extended_private_size::<T::Data>(),
+                                extended_init::<T::Data>,
+                                extended_copy::<T::Data>,
+                                extended_clear::<T::Data>)
+    };
+    assert!(raw != 0, "failed to register error domain \"{}\"", name);
+    unsafe { Quark::from_raw(raw) }
+}
+
+// The domain for errors converted from Rust error values,
+// which are kept in the extended data.
+#[cfg(feature = "v2_68")]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RustError {
+    Failed = 0
+}
+
+#[cfg(feature = "v2_68")]
+impl IntrospectedEnum for RustError {
+
+    fn from_int(v: gint) -> Result<Self, enumeration::UnknownValue> {
+        match v {
+            0 => Ok(RustError::Failed),
+            _ => Err(enumeration::UnknownValue(v))
+        }
+    }
+
+    fn to_int(&self) -> gint {
+        *self as gint
+    }
+
+    fn name(&self) -> &'static str {
+        match *self {
+            RustError::Failed => "failed"
+        }
+    }
+}
+
+#[cfg(feature = "v2_68")]
+g_extended_error_domain!(RustError, Arc<ErrorTrait + Send + Sync>,
+                         "grust-rust-error");
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FileError {
+    Exist = 0,
+    Isdir = 1,
+    Acces = 2,
+    Nametoolong = 3,
+    Noent = 4,
+    Notdir = 5,
+    Nxio = 6,
+    Nodev = 7,
+    Rofs = 8,
+    Txtbsy = 9,
+    Fault = 10,
+    Loop = 11,
+    Nospc = 12,
+    Nomem = 13,
+    Mfile = 14,
+    Nfile = 15,
+    Badf = 16,
+    Inval = 17,
+    Pipe = 18,
+    Again = 19,
+    Intr = 20,
+    Io = 21,
+    Perm = 22,
+    Nosys = 23,
+    Failed = 24
+}
+
+const FILE_ERRORS: [(FileError, &'static str); 25] = [
+    (FileError::Exist, "exist"),
+    (FileError::Isdir, "isdir"),
+    (FileError::Acces, "acces"),
+    (FileError::Nametoolong, "nametoolong"),
+    (FileError::Noent, "noent"),
+    (FileError::Notdir, "notdir"),
+    (FileError::Nxio, "nxio"),
+    (FileError::Nodev, "nodev"),
+    (FileError::Rofs, "rofs"),
+    (FileError::Txtbsy, "txtbsy"),
+    (FileError::Fault, "fault"),
+    (FileError::Loop, "loop"),
+    (FileError::Nospc, "nospc"),
+    (FileError::Nomem, "nomem"),
+    (FileError::Mfile, "mfile"),
+    (FileError::Nfile, "nfile"),
+    (FileError::Badf, "badf"),
+    (FileError::Inval, "inval"),
+    (FileError::Pipe, "pipe"),
+    (FileError::Again, "again"),
+    (FileError::Intr, "intr"),
+    (FileError::Io, "io"),
+    (FileError::Perm, "perm"),
+    (FileError::Nosys, "nosys"),
+    (FileError::Failed, "failed")
+];
+
+impl FileError {
+
+    pub fn from_errno(errno: gint) -> FileError {
+        let code = unsafe { ffi::g_file_error_from_errno(errno) } as gint;
+        FileError::from_int(code).unwrap_or(FileError::Failed)
+    }
+
+    fn from_io_kind(kind: io::ErrorKind) -> FileError {
+        use std::io::ErrorKind::*;
+        match kind {
+            NotFound          => FileError::Noent,
+            PermissionDenied  => FileError::Acces,
+            AlreadyExists     => FileError::Exist,
+            WouldBlock        => FileError::Again,
+            InvalidInput      => FileError::Inval,
+            BrokenPipe        => FileError::Pipe,
+            Interrupted       => FileError::Intr,
+            _                 => FileError::Failed
+        }
+    }
+
+    fn to_io_kind(&self) -> io::ErrorKind {
+        use std::io::ErrorKind;
+        match *self {
+            FileError::Exist => ErrorKind::AlreadyExists,
+            FileError::Noent => ErrorKind::NotFound,
+            FileError::Acces | FileError::Perm | FileError::Rofs
+                => ErrorKind::PermissionDenied,
+            FileError::Inval | FileError::Nametoolong
+                => ErrorKind::InvalidInput,
+            FileError::Pipe  => ErrorKind::BrokenPipe,
+            FileError::Again => ErrorKind::WouldBlock,
+            FileError::Intr  => ErrorKind::Interrupted,
+            _ => ErrorKind::Other
+        }
+    }
+}
+
+impl IntrospectedEnum for FileError {
+
+    fn from_int(v: gint) -> Result<Self, enumeration::UnknownValue> {
+        if v >= 0 && (v as usize) < FILE_ERRORS.len() {
+            Ok(FILE_ERRORS[v as usize].0)
+        } else {
+            Err(enumeration::UnknownValue(v))
+        }
+    }
+
+    fn to_int(&self) -> gint {
+        *self as gint
+    }
+
+    fn name(&self) -> &'static str {
+        FILE_ERRORS[*self as usize].1
+    }
+}
+
+impl Domain for FileError {
+    fn domain() -> Quark {
+        unsafe { Quark::from_raw(ffi::g_file_error_quark()) }
+    }
+}
+
 unsafe impl Send for Error { }
 
 unsafe impl<T> Send for DomainError<T> { }
@@ -99,13 +333,130 @@ impl BoxedType for Error {
     }
 }
 
+// Like a C string would, the message gets truncated at the first NUL
+fn message_to_c_string(message: &str) -> CString {
+    let bytes = message.as_bytes();
+    let len = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    CString::new(&bytes[..len]).unwrap()
+}
+
 impl Error {
 
+    pub fn new<T>(code: T, message: &str) -> Error where T: Domain {
+        let msg = message_to_c_string(message);
+        unsafe {
+            let raw = ffi::g_error_new_literal(domain::<T>().to_raw(),
+                                               code.to_int(),
+                                               msg.as_ptr());
+            Error::from_raw(raw)
+        }
+    }
+
+    pub fn new_fmt<T>(code: T, args: fmt::Arguments) -> Error
+        where T: Domain
+    {
+        Error::new(code, &fmt::format(args))
+    }
+
+    #[cfg(feature = "v2_68")]
+    pub fn new_extended<T>(code: T, message: &str, data: T::Data) -> Error
+        where T: ExtendedDomain
+    {
+        let mut err = Error::new(code, message);
+        err.set_extended_data::<T>(data);
+        err
+    }
+
+    #[cfg(feature = "v2_68")]
+    pub fn extended_data<T>(&self) -> Option<&T::Data>
+        where T: ExtendedDomain
+    {
+        if !self.in_domain::<T>() {
+            return None;
+        }
+        unsafe { (*extended_data_ptr::<T::Data>(self.ptr)).as_ref() }
+    }
+
+    #[cfg(feature = "v2_68")]
+    pub fn set_extended_data<T>(&mut self, data: T::Data)
+        where T: ExtendedDomain
+    {
+        assert!(self.in_domain::<T>(),
+                "error is not in the extended domain {:?}", domain::<T>());
+        unsafe { *extended_data_ptr::<T::Data>(self.ptr) = Some(data); }
+    }
+
+    #[cfg(feature = "v2_68")]
+    pub fn from_std<E>(err: E) -> Error
+        where E: ErrorTrait + Send + Sync + 'static
+    {
+        let message = err.to_string();
+        Error::new_extended(RustError::Failed, &message, Arc::new(err))
+    }
+
+    #[cfg(feature = "v2_68")]
+    pub fn wrapped(&self) -> Option<&(ErrorTrait + Send + Sync + 'static)> {
+        self.extended_data::<RustError>().map(|err| &**err)
+    }
+
+    #[cfg(feature = "v2_68")]
+    fn wrapped_io_kind(&self) -> Option<io::ErrorKind> {
+        self.wrapped().map(|wrapped| {
+            match wrapped.downcast_ref::<io::Error>() {
+                Some(io_err) => io_err.kind(),
+                None         => io::ErrorKind::Other
+            }
+        })
+    }
+
+    #[cfg(not(feature = "v2_68"))]
+    fn wrapped_io_kind(&self) -> Option<io::ErrorKind> {
+        None
+    }
+
+    pub fn to_io_error(&self) -> io::Error {
+        let kind = if let Some(kind) = self.wrapped_io_kind() {
+            kind
+        } else if self.in_domain::<FileError>() {
+            match FileError::from_int(self.key().1) {
+                Ok(code) => code.to_io_kind(),
+                Err(_)   => io::ErrorKind::Other
+            }
+        } else {
+            io::ErrorKind::Other
+        };
+        let message = String::from_utf8_lossy(self.message_bytes());
+        io::Error::new(kind, message.into_owned())
+    }
+
+    pub fn prefix(&mut self, prefix: &str) {
+        let prefix = message_to_c_string(prefix);
+        let mut bytes = prefix.into_bytes();
+        bytes.extend_from_slice(self.message_bytes());
+        let message = CString::new(bytes).unwrap();
+        unsafe {
+            let raw = &mut *self.ptr;
+            ffi::g_free(raw.message as gpointer);
+            raw.message = ffi::g_strdup(message.as_ptr());
+        }
+    }
+
     pub unsafe fn from_raw(ptr: *mut ffi::GError) -> Error {
         assert!(!ptr.is_null(), "GError pointer is not set");
         Error { ptr: ptr }
     }
 
+    pub fn into_raw(self) -> *mut ffi::GError {
+        let ptr = self.ptr;
+        mem::forget(self);
+        ptr
+    }
+
+    #[inline]
+    pub fn as_ptr(&self) -> *const ffi::GError {
+        self.ptr
+    }
+
     pub fn domain(&self) -> Quark {
         unsafe { Quark::from_raw((*self.ptr).domain) }
     }
@@ -152,13 +503,50 @@ impl Error {
     }
 }
 
+impl<T> DomainError<T> where T: Domain {
+
+    pub fn new(code: T, message: &str) -> DomainError<T> {
+        DomainError { inner: Error::new(code, message), marker: PhantomData }
+    }
+
+    pub fn new_fmt(code: T, args: fmt::Arguments) -> DomainError<T> {
+        DomainError { inner: Error::new_fmt(code, args), marker: PhantomData }
+    }
+
+    pub fn into_raw(self) -> *mut ffi::GError {
+        self.inner.into_raw()
+    }
+
+    pub fn prefix(&mut self, prefix: &str) {
+        self.inner.prefix(prefix)
+    }
+}
+
+#[cfg(feature = "v2_68")]
+impl<T> DomainError<T> where T: ExtendedDomain {
+
+    pub fn new_extended(code: T, message: &str, data: T::Data)
+                        -> DomainError<T>
+    {
+        DomainError {
+            inner: Error::new_extended(code, message, data),
+            marker: PhantomData
+        }
+    }
+
+    pub fn extended_data(&self) -> Option<&T::Data> {
+        unsafe { (*extended_data_ptr::<T::Data>(self.inner.ptr)).as_ref() }
+    }
+
+    pub fn set_extended_data(&mut self, data: T::Data) {
+        unsafe { *extended_data_ptr::<T::Data>(self.inner.ptr) = Some(data); }
+    }
+}
+
 impl<T> DomainError<T> where T: IntrospectedEnum {
     pub fn code(&self) -> Code<T> {
         let code = unsafe { (*self.inner.ptr).code };
-        match enumeration::from_int(code) {
-            Ok(domain_code) => Code::Known(domain_code),
-            Err(_)          => Code::Unknown(code)
-        }
+        Code::from_int(code)
     }
 }
 
@@ -172,6 +560,11 @@ impl ErrorTrait for Error {
             Err(_) => "GError (message and domain are not represented)"
         }
     }
+
+    #[cfg(feature = "v2_68")]
+    fn source(&self) -> Option<&(ErrorTrait + 'static)> {
+        self.wrapped().map(|err| err as &(ErrorTrait + 'static))
+    }
 }
 
 impl<T> ErrorTrait for DomainError<T> where T: Domain {
@@ -187,6 +580,11 @@ impl<T> ErrorTrait for DomainError<T> where T: Domain {
             Err(_) => "GError (message and domain are not represented; unknown code)"
         }
     }
+
+    #[cfg(feature = "v2_68")]
+    fn source(&self) -> Option<&(ErrorTrait + 'static)> {
+        self.inner.source()
+    }
 }
 
 impl fmt::Display for Error {
@@ -236,23 +634,79 @@ impl<T> From<DomainError<T>> for Error {
     fn from(err: DomainError<T>) -> Error { err.inner }
 }
 
-impl<T> Code<T> {
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        let code = match err.raw_os_error() {
+            Some(errno) => FileError::from_errno(errno),
+            None        => FileError::from_io_kind(err.kind())
+        };
+        Error::new(code, &err.to_string())
+    }
+}
 
-    pub fn known(self) -> Option<T> {
-        match self {
-            Code::Known(code) => Some(code),
-            Code::Unknown(_) => None
-        }
+fn unreported_error() -> Error {
+    let msg = g_str!("function failed without reporting an error");
+    unsafe {
+        let raw = ffi::g_error_new_literal(
+                g_static_quark!("grust-unreported-error").to_raw(),
+                0, msg.as_ptr());
+        Error::from_raw(raw)
     }
 }
 
-impl<T> PartialEq for Code<T> where T: PartialEq {
-    fn eq(&self, other: &Code<T>) -> bool {
-        match (self, other) {
-            (&Code::Known(ref a), &Code::Known(ref b)) => *a == *b,
-            (&Code::Unknown(a), &Code::Unknown(b)) => a == b,
-            _ => false
+// Calls a function taking a GError out parameter, for functions that
+// do not signal failure with the return value.
+pub fn call<R, F>(f: F) -> Result<R, Error>
+    where F: FnOnce(*mut *mut ffi::GError) -> R
+{
+    let mut err: *mut ffi::GError = ptr::null_mut();
+    let ret = f(&mut err);
+    if err.is_null() {
+        Ok(ret)
+    } else {
+        Err(unsafe { Error::from_raw(err) })
+    }
+}
+
+// Checks the GLib convention that a function returns FALSE
+// if and only if it sets the error.
+pub fn call_bool<F>(f: F) -> Result<(), Error>
+    where F: FnOnce(*mut *mut ffi::GError) -> gboolean
+{
+    let mut err: *mut ffi::GError = ptr::null_mut();
+    let ret = f(&mut err);
+    check_call_result(ret == FALSE, err).map(|_| ())
+}
+
+// Checks the GLib convention that a function returns NULL
+// if and only if it sets the error.
+pub fn call_ptr<T, F>(f: F) -> Result<*mut T, Error>
+    where F: FnOnce(*mut *mut ffi::GError) -> *mut T
+{
+    let mut err: *mut ffi::GError = ptr::null_mut();
+    let ret = f(&mut err);
+    check_call_result(ret.is_null(), err).map(|_| ret)
+}
+
+fn check_call_result(failed: bool, err: *mut ffi::GError)
+                    -> Result<(), Error>
+{
+    if failed {
+        debug_assert!(!err.is_null(),
+                      "function signalled failure without setting the error");
+        if err.is_null() {
+            return Err(unreported_error());
+        }
+        Err(unsafe { Error::from_raw(err) })
+    } else {
+        if !err.is_null() {
+            // Take ownership so the error is freed in any case
+            let err = unsafe { Error::from_raw(err) };
+            debug_assert!(false,
+                          "function set an error without signalling failure: {:?}",
+                          err);
         }
+        Ok(())
     }
 }
 
@@ -16,21 +16,164 @@
 // License along with this library; if not, write to the Free Software
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 
+use gtype;
 use gtype::GType;
-use types::guint;
+use types::{gpointer, guint};
+use util::str_from_ptr;
+
+use gobject as ffi;
 
 use std::error::Error as ErrorTrait;
+use std::ffi::CString;
 use std::fmt;
+use std::marker::PhantomData;
+use std::ptr;
+use std::slice;
 
 pub trait IntrospectedFlags : Sized {
     fn from_uint(v: guint) -> Result<Self, UnknownFlags>;
     fn to_uint(&self) -> guint;
+
+    fn empty() -> Self {
+        from_known_bits(0)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.to_uint() == 0
+    }
+
+    fn contains(&self, other: &Self) -> bool {
+        let bits = other.to_uint();
+        self.to_uint() & bits == bits
+    }
+
+    fn intersects(&self, other: &Self) -> bool {
+        self.to_uint() & other.to_uint() != 0
+    }
+
+    fn insert(&mut self, other: Self) {
+        *self = from_known_bits(self.to_uint() | other.to_uint());
+    }
+
+    fn remove(&mut self, other: Self) {
+        *self = from_known_bits(self.to_uint() & !other.to_uint());
+    }
+
+    fn toggle(&mut self, other: Self) {
+        *self = from_known_bits(self.to_uint() ^ other.to_uint());
+    }
 }
 
 pub trait FlagsType : IntrospectedFlags {
     fn get_type() -> GType;
 }
 
+pub trait FlagsValues : IntrospectedFlags {
+    fn values() -> &'static [ValueDef];
+
+    fn all() -> Self {
+        from_known_bits(known_mask::<Self>())
+    }
+
+    fn iter(&self) -> Iter<Self> {
+        Iter {
+            defs: Self::values().iter(),
+            bits: self.to_uint(),
+            marker: PhantomData
+        }
+    }
+}
+
+// Flags that may have bits unknown to the bindings, such as ones
+// added in a newer version of a C library. The unknown bits are
+// preserved so that the value can be passed back.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Lenient<F> {
+    known: F,
+    unknown: guint
+}
+
+impl<F> Lenient<F> where F: IntrospectedFlags {
+
+    pub fn from_uint(v: guint) -> Lenient<F> {
+        match F::from_uint(v) {
+            Ok(known) => Lenient { known: known, unknown: 0 },
+            Err(err) => Lenient {
+                known: from_known_bits(err.known()),
+                unknown: err.unknown()
+            }
+        }
+    }
+
+    pub fn to_uint(&self) -> guint {
+        self.known.to_uint() | self.unknown
+    }
+}
+
+impl<F> Lenient<F> {
+
+    pub fn known(&self) -> &F {
+        &self.known
+    }
+
+    pub fn into_known(self) -> F {
+        self.known
+    }
+
+    pub fn unknown(&self) -> guint {
+        self.unknown
+    }
+}
+
+impl<F> From<F> for Lenient<F> where F: IntrospectedFlags {
+    fn from(val: F) -> Lenient<F> {
+        Lenient { known: val, unknown: 0 }
+    }
+}
+
+pub fn known_mask<F>() -> guint where F: FlagsValues {
+    F::values().iter().fold(0, |mask, def| mask | def.value)
+}
+
+// Combinations of known flags are expected to be accepted by from_uint()
+pub fn from_known_bits<F>(bits: guint) -> F where F: IntrospectedFlags {
+    match F::from_uint(bits) {
+        Ok(flags) => flags,
+        Err(err) => panic!("combination of known flags was rejected: {}", err)
+    }
+}
+
+// Iterates over the values set in the flags, as defined in FlagsValues
+pub struct Iter<F> {
+    defs: slice::Iter<'static, ValueDef>,
+    bits: guint,
+    marker: PhantomData<F>
+}
+
+impl<F> Iterator for Iter<F> where F: IntrospectedFlags {
+    type Item = F;
+
+    fn next(&mut self) -> Option<F> {
+        // Like to_string(), each bit is only yielded with the first value
+        // containing it, so composite values do not repeat the bits
+        // of values listed before them
+        for def in self.defs.by_ref() {
+            if def.value != 0 && self.bits & def.value == def.value {
+                self.bits &= !def.value;
+                return Some(from_known_bits(def.value));
+            }
+        }
+        None
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct ValueDef {
+    pub value: guint,
+    pub name: &'static str,
+    pub nick: &'static str
+}
+
 #[derive(Copy, Clone)]
 pub struct UnknownFlags {
     actual: guint,
@@ -82,3 +225,255 @@ pub fn from_uint<F>(v: guint) -> Result<F, UnknownFlags>
 pub fn type_of<F>() -> GType where F: FlagsType {
     <F as FlagsType>::get_type()
 }
+
+// The value table and its strings must live as long as the type,
+// so they are leaked.
+pub fn register_flags_type<F>(name: &str) -> GType where F: FlagsValues {
+    let mut table: Vec<ffi::GFlagsValue> = F::values().iter().map(|def| {
+        ffi::GFlagsValue {
+            value: def.value,
+            value_name: CString::new(def.name).unwrap().into_raw(),
+            value_nick: CString::new(def.nick).unwrap().into_raw()
+        }
+    }).collect();
+    table.push(ffi::GFlagsValue {
+        value: 0,
+        value_name: ptr::null(),
+        value_nick: ptr::null()
+    });
+    let table = Box::into_raw(table.into_boxed_slice());
+    let c_name = CString::new(name).unwrap();
+    let raw = unsafe {
+        ffi::g_flags_register_static(c_name.as_ptr(),
+                                     table as *const ffi::GFlagsValue)
+    };
+    assert!(raw != 0, "failed to register type \"{}\"", name);
+    unsafe { GType::from_raw(raw) }
+}
+
+pub struct FlagsClass {
+    raw: *mut ffi::GFlagsClass
+}
+
+unsafe impl Send for FlagsClass { }
+unsafe impl Sync for FlagsClass { }
+
+#[repr(C)]
+pub struct FlagsValue(ffi::GFlagsValue);
+
+impl FlagsClass {
+
+    pub fn from_type(gtype: GType) -> Option<FlagsClass> {
+        unsafe {
+            // The abstract base class has no values
+            if gtype == gtype::FLAGS {
+                return None;
+            }
+            if ffi::g_type_fundamental(gtype.to_raw()) != gtype::FLAGS.to_raw() {
+                return None;
+            }
+            let raw = ffi::g_type_class_ref(gtype.to_raw());
+            Some(FlagsClass { raw: raw as *mut ffi::GFlagsClass })
+        }
+    }
+
+    pub fn of<F>() -> FlagsClass where F: FlagsType {
+        FlagsClass::from_type(type_of::<F>())
+            .expect("FlagsType::get_type() did not return a flags type")
+    }
+
+    pub fn type_of(&self) -> GType {
+        unsafe { GType::from_raw((*self.raw).g_type_class.g_type) }
+    }
+
+    pub fn mask(&self) -> guint {
+        unsafe { (*self.raw).mask }
+    }
+
+    pub fn values(&self) -> &[FlagsValue] {
+        unsafe {
+            let raw = &*self.raw;
+            slice::from_raw_parts(raw.values as *const FlagsValue,
+                                  raw.n_values as usize)
+        }
+    }
+
+    pub fn first_value(&self, value: guint) -> Option<&FlagsValue> {
+        unsafe {
+            let p = ffi::g_flags_get_first_value(self.raw, value);
+            (p as *const FlagsValue).as_ref()
+        }
+    }
+
+    pub fn value_by_name(&self, name: &str) -> Option<&FlagsValue> {
+        let name = match CString::new(name) {
+            Ok(s) => s,
+            Err(_) => return None
+        };
+        unsafe {
+            let p = ffi::g_flags_get_value_by_name(self.raw, name.as_ptr());
+            (p as *const FlagsValue).as_ref()
+        }
+    }
+
+    pub fn value_by_nick(&self, nick: &str) -> Option<&FlagsValue> {
+        let nick = match CString::new(nick) {
+            Ok(s) => s,
+            Err(_) => return None
+        };
+        unsafe {
+            let p = ffi::g_flags_get_value_by_nick(self.raw, nick.as_ptr());
+            (p as *const FlagsValue).as_ref()
+        }
+    }
+
+    // Splits the bit set into the named values covering it,
+    // returning the bits not covered by any value as the remainder.
+    pub fn decompose(&self, value: guint) -> (Vec<&FlagsValue>, guint) {
+        let mut found = Vec::new();
+        let mut rest = value;
+        while rest != 0 {
+            match self.first_value(rest) {
+                Some(v) if v.value() != 0 => {
+                    rest &= !v.value();
+                    found.push(v);
+                }
+                _ => break
+            }
+        }
+        (found, rest)
+    }
+}
+
+impl Clone for FlagsClass {
+    fn clone(&self) -> FlagsClass {
+        unsafe {
+            let raw = ffi::g_type_class_ref(self.type_of().to_raw());
+            FlagsClass { raw: raw as *mut ffi::GFlagsClass }
+        }
+    }
+}
+
+impl Drop for FlagsClass {
+    fn drop(&mut self) {
+        unsafe { ffi::g_type_class_unref(self.raw as gpointer); }
+    }
+}
+
+impl fmt::Debug for FlagsClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "FlagsClass({:?})", self.type_of())
+    }
+}
+
+impl FlagsValue {
+
+    pub fn value(&self) -> guint {
+        self.0.value
+    }
+
+    pub fn name(&self) -> &str {
+        unsafe { str_from_ptr(self.0.value_name) }
+    }
+
+    pub fn nick(&self) -> &str {
+        unsafe { str_from_ptr(self.0.value_nick) }
+    }
+}
+
+impl fmt::Debug for FlagsValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "FlagsValue {{ value: 0b{:b}, name: {:?}, nick: {:?} }}",
+               self.value(), self.name(), self.nick())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum ParseFlagsError {
+    UnknownNames(Vec<String>),
+    UnknownFlags(UnknownFlags)
+}
+
+impl ErrorTrait for ParseFlagsError {
+    fn description(&self) -> &str {
+        match *self {
+            ParseFlagsError::UnknownNames(_) => "unknown flag names",
+            ParseFlagsError::UnknownFlags(ref err) => err.description()
+        }
+    }
+}
+
+impl fmt::Display for ParseFlagsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseFlagsError::UnknownNames(ref names) => {
+                write!(f, "unknown flag names: {}", names.join(", "))
+            }
+            ParseFlagsError::UnknownFlags(ref err) => err.fmt(f)
+        }
+    }
+}
+
+impl From<UnknownFlags> for ParseFlagsError {
+    fn from(err: UnknownFlags) -> ParseFlagsError {
+        ParseFlagsError::UnknownFlags(err)
+    }
+}
+
+// Formats the flags as nicks separated with " | ".
+// Bits not covered by any value are given as a hexadecimal number.
+pub fn to_string<F>(flags: &F) -> String where F: FlagsValues {
+    let defs = F::values();
+    let value = flags.to_uint();
+    if value == 0 {
+        return match defs.iter().find(|def| def.value == 0) {
+            Some(def) => def.nick.to_string(),
+            None => "0".to_string()
+        };
+    }
+    let mut rest = value;
+    let mut parts = Vec::new();
+    // Same as decomposing with the first matching value in turn,
+    // as done by g_flags_get_first_value()
+    for def in defs.iter() {
+        if def.value != 0 && rest & def.value == def.value {
+            rest &= !def.value;
+            parts.push(def.nick.to_string());
+        }
+    }
+    if rest != 0 {
+        parts.push(format!("0x{:x}", rest));
+    }
+    parts.join(" | ")
+}
+
+fn parse_number(s: &str) -> Option<guint> {
+    if s.starts_with("0x") || s.starts_with("0X") {
+        guint::from_str_radix(&s[2..], 16).ok()
+    } else {
+        s.parse().ok()
+    }
+}
+
+// Parses flags separated with '|', given by value names, nicks
+// or numeric literals.
+pub fn parse<F>(s: &str) -> Result<F, ParseFlagsError> where F: FlagsValues {
+    let defs = F::values();
+    let mut value = 0;
+    let mut unknown = Vec::new();
+    for token in s.split('|').map(|t| t.trim()).filter(|t| !t.is_empty()) {
+        let found = defs.iter()
+                        .find(|def| def.nick == token)
+                        .or_else(|| defs.iter().find(|def| def.name == token))
+                        .map(|def| def.value)
+                        .or_else(|| parse_number(token));
+        match found {
+            Some(bits) => value |= bits,
+            None => unknown.push(token.to_string())
+        }
+    }
+    if !unknown.is_empty() {
+        return Err(ParseFlagsError::UnknownNames(unknown));
+    }
+    F::from_uint(value).map_err(ParseFlagsError::from)
+}
@@ -19,10 +19,6 @@
 #![crate_name = "grust"]
 #![crate_type = "lib"]
 
-#![allow(unstable_features)]
-
-#![feature(unsafe_no_drop_flag)]
-
 extern crate libc;
 extern crate gtypes;
 extern crate glib_2_0_sys as glib;
@@ -32,6 +28,7 @@ extern crate gobject_2_0_sys as gobject;
 mod macros;
 
 pub mod boxed;
+pub mod datalist;
 pub mod enumeration;
 pub mod error;
 pub mod flags;
@@ -39,8 +36,11 @@ pub mod gstr;
 pub mod gtype;
 pub mod mainloop;
 pub mod object;
+pub mod panic;
 pub mod quark;
 pub mod refcount;
+pub mod testclock;
+pub mod threadpool;
 pub mod types;
 pub mod util;
 pub mod value;
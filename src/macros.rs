@@ -33,50 +33,158 @@ macro_rules! g_utf8 {
     }
 }
 
+#[macro_export]
+macro_rules! g_error_new {
+    ($code:expr, $($arg:tt)*) => {
+        $crate::error::Error::new_fmt($code, format_args!($($arg)*))
+    }
+}
+
 #[macro_export]
 macro_rules! g_error_match {
+    // Collect the arms into a list of {(slot: type) [filter] => handler}
+    (@parse $err:ident [$($arms:tt)*] , $($rest:tt)*) => {
+        g_error_match!(@parse $err [$($arms)*] $($rest)*)
+    };
+    (
+        @parse $err:ident [$($arms:tt)*]
+        ($slot:ident : $errtype:ty) if code $($code:pat)|+ => $handler:expr,
+        $($rest:tt)*
+    ) => {
+        g_error_match!(@parse $err [
+            $($arms)* {($slot: $errtype) [code $($code)|+] => $handler}
+        ] $($rest)*)
+    };
+    (
+        @parse $err:ident [$($arms:tt)*]
+        ($slot:ident : $errtype:ty) if unknown code => $handler:expr,
+        $($rest:tt)*
+    ) => {
+        g_error_match!(@parse $err [
+            $($arms)* {($slot: $errtype) [unknown] => $handler}
+        ] $($rest)*)
+    };
+    (
+        @parse $err:ident [$($arms:tt)*]
+        ($slot:ident : $errtype:ty) if $guard:expr => $handler:expr,
+        $($rest:tt)*
+    ) => {
+        g_error_match!(@parse $err [
+            $($arms)* {($slot: $errtype) [guard $guard] => $handler}
+        ] $($rest)*)
+    };
+    (
+        @parse $err:ident [$($arms:tt)*]
+        ($slot:ident : $errtype:ty) => $handler:expr,
+        $($rest:tt)*
+    ) => {
+        g_error_match!(@parse $err [
+            $($arms)* {($slot: $errtype) [any] => $handler}
+        ] $($rest)*)
+    };
     (
-        ($inp:expr) {
-            ($slot:ident : $errtype:ty) => $handler:expr,
-            $(($slot_tail:ident : $errtype_tail:ty) => $handler_tail:expr,)*
-            other $catchall_slot:ident => $catchall_handler:expr
+        @parse $err:ident [$($arms:tt)*]
+        other $catchall_slot:ident => $catchall_handler:expr, $(,)*
+    ) => {
+        g_error_match!(@emit $err [other $catchall_slot $catchall_handler];
+                       $($arms)*)
+    };
+    (@parse $err:ident [$($arms:tt)*]) => {
+        g_error_match!(@emit $err [result]; $($arms)*)
+    };
+
+    // Expand the collected arms into nested matches
+    (@emit $err:ident [other $slot:ident $handler:expr];) => {
+        {
+            let $slot: $crate::error::Error = $err;
+            $handler
         }
+    };
+    (@emit $err:ident [result];) => {
+        ::std::result::Result::Err($err)
+    };
+    (
+        @emit $err:ident $mode:tt;
+        {($slot:ident : $errtype:ty) $filter:tt => $handler:expr}
+        $($arms:tt)*
     ) => {
         {
-            let err: $crate::error::Error = $inp;
             let res: ::std::result::Result<$errtype, $crate::error::Error>
-                     = err.into_domain();
-            match res {
-                Ok($slot) => $handler,
-                Err(e) => g_error_match! {
-                    (e) {
-                        $(($slot_tail: $errtype_tail) => $handler_tail,)*
-                        other $catchall_slot => $catchall_handler
+                     = $err.into_domain();
+            match g_error_match!(@filter res $slot $filter) {
+                ::std::result::Result::Ok($slot) => {
+                    g_error_match!(@wrap $mode $handler)
+                }
+                ::std::result::Result::Err($err) => {
+                    g_error_match!(@emit $err $mode; $($arms)*)
+                }
+            }
+        }
+    };
+
+    (@filter $res:ident $slot:ident [any]) => { $res };
+    (@filter $res:ident $slot:ident [code $($code:pat)|+]) => {
+        match $res {
+            ::std::result::Result::Ok(e) => {
+                match e.code() {
+                    $($crate::error::Code::Known($code))|+ => {
+                        ::std::result::Result::Ok(e)
                     }
+                    _ => ::std::result::Result::Err($crate::error::Error::from(e))
                 }
             }
+            ::std::result::Result::Err(e) => ::std::result::Result::Err(e)
         }
     };
-    (
-        ($inp:expr) {
-            other $catchall_slot:ident => $catchall_handler:expr
+    (@filter $res:ident $slot:ident [unknown]) => {
+        match $res {
+            ::std::result::Result::Ok(e) => {
+                match e.code() {
+                    $crate::error::Code::Unknown(_) => {
+                        ::std::result::Result::Ok(e)
+                    }
+                    _ => ::std::result::Result::Err($crate::error::Error::from(e))
+                }
+            }
+            ::std::result::Result::Err(e) => ::std::result::Result::Err(e)
         }
-    ) => {
+    };
+    (@filter $res:ident $slot:ident [guard $guard:expr]) => {
+        match $res {
+            ::std::result::Result::Ok($slot) => {
+                if $guard {
+                    ::std::result::Result::Ok($slot)
+                } else {
+                    ::std::result::Result::Err($crate::error::Error::from($slot))
+                }
+            }
+            ::std::result::Result::Err(e) => ::std::result::Result::Err(e)
+        }
+    };
+
+    (@wrap [other $slot:ident $handler:expr] $e:expr) => { $e };
+    (@wrap [result] $e:expr) => {
         {
-            let $catchall_slot: $crate::error::Error = $inp;
-            $catchall_handler
+            #[allow(unreachable_code)]
+            let r = ::std::result::Result::Ok($e);
+            r
         }
-    }
+    };
+
+    (($inp:expr) { $($arms:tt)* }) => {
+        {
+            let err: $crate::error::Error = $inp;
+            g_error_match!(@parse err [] $($arms)* ,)
+        }
+    };
 }
 
 #[macro_export]
 macro_rules! g_static_quark {
     ($lit:expr) => {
         {
-            use $crate::quark::StaticQuark;
-
-            static QUARK: StaticQuark =
-                StaticQuark($lit, ::std::sync::atomic::ATOMIC_USIZE_INIT);
+            static QUARK: $crate::quark::StaticQuark =
+                $crate::quark::StaticQuark::new(concat!($lit, "\0"));
 
             QUARK.get()
         }
@@ -88,12 +196,12 @@ macro_rules! g_type_register_box {
     ($t:ty, $name:expr) => {
         unsafe impl $crate::boxed::BoxRegistered for $t {
             fn box_type() -> $crate::gtype::GType {
-                use ::std::sync::atomic::{AtomicUsize, ATOMIC_USIZE_INIT};
+                use ::std::sync::atomic::AtomicUsize;
                 use ::std::sync::atomic::Ordering::{Acquire,Release};
-                use ::std::sync::{Once, ONCE_INIT};
+                use ::std::sync::Once;
 
-                static REGISTERED: AtomicUsize = ATOMIC_USIZE_INIT;
-                static INIT: Once = ONCE_INIT;
+                static REGISTERED: AtomicUsize = AtomicUsize::new(0);
+                static INIT: Once = Once::new();
 
                 INIT.call_once(|| {
                     let gtype = $crate::boxed::register_box_type::<$t>($name);
@@ -108,6 +216,158 @@ macro_rules! g_type_register_box {
     }
 }
 
+#[macro_export]
+macro_rules! g_type_register_enum {
+    ($t:ty, $name:expr) => {
+        impl $crate::enumeration::EnumType for $t {
+            fn get_type() -> $crate::gtype::GType {
+                use ::std::sync::atomic::AtomicUsize;
+                use ::std::sync::atomic::Ordering::{Acquire,Release};
+                use ::std::sync::Once;
+
+                static REGISTERED: AtomicUsize = AtomicUsize::new(0);
+                static INIT: Once = Once::new();
+
+                INIT.call_once(|| {
+                    let gtype = $crate::enumeration::register_enum_type::<$t>($name);
+                    REGISTERED.store(gtype.to_raw() as usize, Release);
+                });
+
+                let raw = REGISTERED.load(Acquire)
+                          as $crate::gtype::raw::GType;
+                unsafe { $crate::gtype::GType::from_raw(raw) }
+            }
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! g_type_register_flags {
+    ($t:ty, $name:expr) => {
+        impl $crate::flags::FlagsType for $t {
+            fn get_type() -> $crate::gtype::GType {
+                use ::std::sync::atomic::AtomicUsize;
+                use ::std::sync::atomic::Ordering::{Acquire,Release};
+                use ::std::sync::Once;
+
+                static REGISTERED: AtomicUsize = AtomicUsize::new(0);
+                static INIT: Once = Once::new();
+
+                INIT.call_once(|| {
+                    let gtype = $crate::flags::register_flags_type::<$t>($name);
+                    REGISTERED.store(gtype.to_raw() as usize, Release);
+                });
+
+                let raw = REGISTERED.load(Acquire)
+                          as $crate::gtype::raw::GType;
+                unsafe { $crate::gtype::GType::from_raw(raw) }
+            }
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! g_impl_flags_ops {
+    ($t:ty) => {
+        impl ::std::ops::BitOr for $t {
+            type Output = $t;
+            fn bitor(self, other: $t) -> $t {
+                use $crate::flags::IntrospectedFlags;
+                $crate::flags::from_known_bits(self.to_uint() | other.to_uint())
+            }
+        }
+
+        impl ::std::ops::BitOrAssign for $t {
+            fn bitor_assign(&mut self, other: $t) {
+                *self = ::std::ops::BitOr::bitor(*self, other);
+            }
+        }
+
+        impl ::std::ops::BitAnd for $t {
+            type Output = $t;
+            fn bitand(self, other: $t) -> $t {
+                use $crate::flags::IntrospectedFlags;
+                $crate::flags::from_known_bits(self.to_uint() & other.to_uint())
+            }
+        }
+
+        impl ::std::ops::BitAndAssign for $t {
+            fn bitand_assign(&mut self, other: $t) {
+                *self = ::std::ops::BitAnd::bitand(*self, other);
+            }
+        }
+
+        impl ::std::ops::BitXor for $t {
+            type Output = $t;
+            fn bitxor(self, other: $t) -> $t {
+                use $crate::flags::IntrospectedFlags;
+                $crate::flags::from_known_bits(self.to_uint() ^ other.to_uint())
+            }
+        }
+
+        impl ::std::ops::BitXorAssign for $t {
+            fn bitxor_assign(&mut self, other: $t) {
+                *self = ::std::ops::BitXor::bitxor(*self, other);
+            }
+        }
+
+        impl ::std::ops::Sub for $t {
+            type Output = $t;
+            fn sub(self, other: $t) -> $t {
+                use $crate::flags::IntrospectedFlags;
+                $crate::flags::from_known_bits(self.to_uint() & !other.to_uint())
+            }
+        }
+
+        impl ::std::ops::SubAssign for $t {
+            fn sub_assign(&mut self, other: $t) {
+                *self = ::std::ops::Sub::sub(*self, other);
+            }
+        }
+
+        impl ::std::ops::Not for $t {
+            type Output = $t;
+            fn not(self) -> $t {
+                use $crate::flags::IntrospectedFlags;
+                let mask = $crate::flags::known_mask::<$t>();
+                $crate::flags::from_known_bits(!self.to_uint() & mask)
+            }
+        }
+    }
+}
+
+// Needs GLib 2.68
+#[cfg(feature = "v2_68")]
+#[macro_export]
+macro_rules! g_extended_error_domain {
+    ($t:ty, $data:ty, $name:expr) => {
+        impl $crate::error::Domain for $t {
+            fn domain() -> $crate::quark::Quark {
+                use ::std::sync::atomic::AtomicUsize;
+                use ::std::sync::atomic::Ordering::{Acquire,Release};
+                use ::std::sync::Once;
+
+                static REGISTERED: AtomicUsize = AtomicUsize::new(0);
+                static INIT: Once = Once::new();
+
+                INIT.call_once(|| {
+                    let quark =
+                        $crate::error::register_extended_domain::<$t>($name);
+                    REGISTERED.store(quark.to_raw() as usize, Release);
+                });
+
+                let raw = REGISTERED.load(Acquire)
+                          as $crate::quark::raw::GQuark;
+                unsafe { $crate::quark::Quark::from_raw(raw) }
+            }
+        }
+
+        impl $crate::error::ExtendedDomain for $t {
+            type Data = $data;
+        }
+    }
+}
+
 #[macro_export]
 macro_rules! g_impl_boxed_type_for_ref {
     ($t:ty, $get_type:path) => {
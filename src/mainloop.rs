@@ -16,18 +16,28 @@
 // License along with this library; if not, write to the Free Software
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 
+use panic;
 use refcount::{Refcount, Ref};
+use testclock;
 use types::{FALSE, TRUE};
 use types::{gboolean, gint, gpointer, guint};
 use util::{box_free, box_from_pointer, box_into_pointer, into_destroy_notify};
+use util::is_true;
 use wrap;
 use wrap::Wrapper;
 
 use glib as ffi;
 use gobject;
+use std::cell::{Cell, RefCell};
 use std::convert;
+use std::ffi::{CStr, CString};
 use std::marker;
 use std::mem;
+use std::ops::{Add, Sub};
+use std::ptr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub const PRIORITY_DEFAULT      : gint = ffi::G_PRIORITY_DEFAULT;
 pub const PRIORITY_DEFAULT_IDLE : gint = ffi::G_PRIORITY_DEFAULT_IDLE;
@@ -56,15 +66,40 @@ unsafe fn into_source_func(func: unsafe extern "C" fn(gpointer) -> gboolean)
     mem::transmute(func)
 }
 
+thread_local!(static RUNNING_LOOPS: RefCell<Vec<*mut ffi::GMainLoop>>
+              = RefCell::new(Vec::new()));
+
+unsafe fn run_loop(mainloop: *mut ffi::GMainLoop) {
+    panic::resume_point(|| {
+        RUNNING_LOOPS.with(|loops| loops.borrow_mut().push(mainloop));
+        ffi::g_main_loop_run(mainloop);
+        RUNNING_LOOPS.with(|loops| { loops.borrow_mut().pop(); });
+    });
+}
+
+// Called after a callback has panicked, to get the panic
+// resumed by the innermost loop running on this thread
+fn quit_running_loop() {
+    RUNNING_LOOPS.with(|loops| {
+        if let Some(&mainloop) = loops.borrow().last() {
+            unsafe { ffi::g_main_loop_quit(mainloop) };
+        }
+    });
+}
+
 unsafe extern "C" fn source_func<F>(callback_data: gpointer) -> gboolean
     where F: FnMut() -> CallbackResult
 {
     let mut callback: Box<F> = box_from_pointer(callback_data);
-    let res = callback();
+    let res = panic::catch(|| callback());
     mem::forget(callback);
     match res {
-        Remove => FALSE,
-        Continue => TRUE
+        Some(Remove) => FALSE,
+        Some(Continue) => TRUE,
+        None => {
+            quit_running_loop();
+            FALSE
+        }
     }
 }
 
@@ -72,14 +107,23 @@ unsafe extern "C" fn source_once_func<F>(callback_data: gpointer) -> gboolean
     where F: FnOnce()
 {
     let mut holder: Box<Option<F>> = box_from_pointer(callback_data);
-    let callback = holder.take().expect("a callback closure expected");
+    let callback = holder.take();
     mem::forget(holder);
-    callback();
+    let res = panic::catch(|| {
+        let callback = callback.expect("a callback closure expected");
+        callback();
+    });
+    if res.is_none() {
+        quit_running_loop();
+    }
     FALSE
 }
 
 pub struct SourceCallback(RawCallback);
 
+// The closures wrapped by SourceCallback are required to be Send
+unsafe impl Send for SourceCallback { }
+
 impl Into<RawCallback> for SourceCallback {
     #[inline]
     fn into(self) -> RawCallback {
@@ -115,6 +159,118 @@ impl SourceCallback {
     }
 }
 
+// A thread-local callback closure, tagged with the thread that has
+// created it. The closure must only be called and dropped on that thread,
+// even if the main context gets released and acquired by another thread.
+struct LocalClosure<F> {
+    owner: thread::ThreadId,
+    closure: F
+}
+
+impl<F> LocalClosure<F> {
+    fn new(closure: F) -> Self {
+        LocalClosure { owner: thread::current().id(), closure: closure }
+    }
+
+    fn check_thread(&self) {
+        if thread::current().id() != self.owner {
+            // Unwinding from here would cross into C,
+            // and going on would be unsound
+            panic::catch_or_abort(|| {
+                panic!("a thread-local callback is used on a thread \
+                        other than the one that created it")
+            })
+        }
+    }
+}
+
+unsafe extern "C" fn local_source_func<F>(callback_data: gpointer) -> gboolean
+    where F: FnMut() -> CallbackResult
+{
+    let mut local: Box<LocalClosure<F>> = box_from_pointer(callback_data);
+    local.check_thread();
+    let res = panic::catch(|| (local.closure)());
+    mem::forget(local);
+    match res {
+        Some(Remove) => FALSE,
+        Some(Continue) => TRUE,
+        None => {
+            quit_running_loop();
+            FALSE
+        }
+    }
+}
+
+unsafe extern "C" fn local_source_once_func<F>(callback_data: gpointer)
+                                              -> gboolean
+    where F: FnOnce()
+{
+    let mut local: Box<LocalClosure<Option<F>>> =
+            box_from_pointer(callback_data);
+    local.check_thread();
+    let callback = local.closure.take();
+    mem::forget(local);
+    let res = panic::catch(|| {
+        let callback = callback.expect("a callback closure expected");
+        callback();
+    });
+    if res.is_none() {
+        quit_running_loop();
+    }
+    FALSE
+}
+
+unsafe extern "C" fn local_closure_free<F>(raw: gpointer) {
+    {
+        let local: &LocalClosure<F> = &*(raw as *const LocalClosure<F>);
+        local.check_thread();
+    }
+    box_free::<LocalClosure<F>>(raw);
+}
+
+pub struct LocalSourceCallback(RawCallback);
+
+impl Into<RawCallback> for LocalSourceCallback {
+    #[inline]
+    fn into(self) -> RawCallback {
+        self.0
+    }
+}
+
+impl LocalSourceCallback {
+    pub fn new<F>(closure: F) -> Self
+        where F: 'static, F: FnMut() -> CallbackResult
+    {
+        let local = Box::new(LocalClosure::new(closure));
+        LocalSourceCallback(unsafe {
+            RawCallback {
+                func: into_source_func(local_source_func::<F>),
+                data: box_into_pointer(local),
+                destroy: into_destroy_notify(local_closure_free::<F>)
+            }
+        })
+    }
+
+    pub fn once<F>(closure: F) -> Self
+        where F: 'static, F: FnOnce()
+    {
+        let local = Box::new(LocalClosure::new(Some(closure)));
+        LocalSourceCallback(unsafe {
+            RawCallback {
+                func: into_source_func(local_source_once_func::<F>),
+                data: box_into_pointer(local),
+                destroy: into_destroy_notify(local_closure_free::<Option<F>>)
+            }
+        })
+    }
+}
+
+fn assert_local_context(ctx: &MainContext) {
+    assert!(ctx.is_owner(),
+            "a thread-local callback can only be used with a main context \
+             owned by the current thread");
+}
+
 #[repr(C)]
 pub struct MainContext {
     raw: ffi::GMainContext
@@ -127,23 +283,114 @@ unsafe impl Wrapper for MainContext {
 }
 
 impl MainContext {
+    pub fn new() -> Ref<MainContext> {
+        unsafe {
+            let ctx = ffi::g_main_context_new();
+            Ref::from_raw(ctx)
+        }
+    }
+
     pub fn default() -> &'static MainContext {
         unsafe {
             wrap::from_raw(ffi::g_main_context_default())
         }
     }
 
+    pub fn thread_default() -> Option<Ref<MainContext>> {
+        unsafe {
+            let ctx = ffi::g_main_context_get_thread_default();
+            if ctx.is_null() {
+                return None;
+            }
+            Some(Ref::new(wrap::from_raw(ctx)))
+        }
+    }
+
+    pub fn ref_thread_default() -> Ref<MainContext> {
+        unsafe {
+            let ctx = ffi::g_main_context_ref_thread_default();
+            Ref::from_raw(ctx)
+        }
+    }
+
+    pub fn iteration(&self, may_block: bool) -> bool {
+        let dispatched = panic::resume_point(|| unsafe {
+            ffi::g_main_context_iteration(self.as_mut_ptr(),
+                                          may_block as gboolean)
+        });
+        is_true(dispatched)
+    }
+
+    pub fn pending(&self) -> bool {
+        is_true(unsafe { ffi::g_main_context_pending(self.as_mut_ptr()) })
+    }
+
+    pub fn wakeup(&self) {
+        unsafe { ffi::g_main_context_wakeup(self.as_mut_ptr()) }
+    }
+
+    pub fn acquire(&self) -> Option<AcquireGuard> {
+        let acquired = unsafe {
+            ffi::g_main_context_acquire(self.as_mut_ptr())
+        };
+        if is_true(acquired) {
+            Some(AcquireGuard { ctx: self, not_send: marker::PhantomData })
+        } else {
+            None
+        }
+    }
+
+    pub fn is_owner(&self) -> bool {
+        is_true(unsafe { ffi::g_main_context_is_owner(self.as_mut_ptr()) })
+    }
+
+    // The callback type of a source found by its ID is not known,
+    // so the source is treated as one with a thread-local callback:
+    // the context must be owned by the current thread, and the returned
+    // source cannot be sent to other threads.
+    pub fn find_source_by_id(&self, id: guint)
+                            -> Option<Ref<AttachedSource<LocalSourceCallback>>>
+    {
+        assert_local_context(self);
+        unsafe {
+            let source = ffi::g_main_context_find_source_by_id(
+                    self.as_mut_ptr(), id);
+            if source.is_null() {
+                return None;
+            }
+            Some(Ref::new(wrap::from_raw(source)))
+        }
+    }
+
+    pub fn push_thread_default(&self) -> ThreadDefaultGuard {
+        unsafe {
+            ffi::g_main_context_push_thread_default(self.as_mut_ptr());
+        }
+        let depth = THREAD_DEFAULT_DEPTH.with(|cell| {
+            let depth = cell.get() + 1;
+            cell.set(depth);
+            depth
+        });
+        ThreadDefaultGuard {
+            ctx: self,
+            depth: depth,
+            not_send: marker::PhantomData
+        }
+    }
+
     pub fn invoke(&self, callback: SourceCallback) {
         self.invoke_full(PRIORITY_DEFAULT, callback)
     }
 
     pub fn invoke_full(&self, priority: gint, callback: SourceCallback) {
         let raw: RawCallback = callback.into();
-        unsafe {
-            ffi::g_main_context_invoke_full(self.as_mut_ptr(),
-                    priority, raw.func, raw.data, Some(raw.destroy));
-        }
+        let (func, data, destroy) = (raw.func, raw.data, raw.destroy);
         mem::forget(raw);
+        // The callback may be called in place
+        panic::resume_point(|| unsafe {
+            ffi::g_main_context_invoke_full(self.as_mut_ptr(),
+                    priority, func, data, Some(destroy));
+        });
     }
 }
 
@@ -160,6 +407,62 @@ impl Refcount for MainContext {
 
 g_impl_boxed_type_for_ref!(MainContext, gobject::g_main_context_get_type);
 
+// Ownership of a GMainContext is held by a thread, so the guard
+// must be released on the thread that has acquired it.
+pub struct AcquireGuard<'a> {
+    ctx: &'a MainContext,
+    not_send: marker::PhantomData<*mut ()>
+}
+
+impl<'a> AcquireGuard<'a> {
+    #[inline]
+    pub fn context(&self) -> &MainContext {
+        self.ctx
+    }
+}
+
+impl<'a> Drop for AcquireGuard<'a> {
+    fn drop(&mut self) {
+        unsafe { ffi::g_main_context_release(self.ctx.as_mut_ptr()) }
+    }
+}
+
+thread_local!(static THREAD_DEFAULT_DEPTH: Cell<usize> = Cell::new(0));
+
+pub struct ThreadDefaultGuard<'a> {
+    ctx: &'a MainContext,
+    depth: usize,
+    not_send: marker::PhantomData<*mut ()>
+}
+
+impl<'a> ThreadDefaultGuard<'a> {
+    #[inline]
+    pub fn context(&self) -> &MainContext {
+        self.ctx
+    }
+}
+
+impl<'a> Drop for ThreadDefaultGuard<'a> {
+    fn drop(&mut self) {
+        let depth = THREAD_DEFAULT_DEPTH.with(|cell| cell.get());
+        if depth != self.depth {
+            // Popping would remove some other context from the stack,
+            // so leave it alone and report the misuse unless
+            // we are already unwinding.
+            if !thread::panicking() {
+                panic!("thread-default main context guard dropped out of order \
+                        (pushed at depth {}, current depth {})",
+                       self.depth, depth);
+            }
+            return;
+        }
+        unsafe {
+            ffi::g_main_context_pop_thread_default(self.ctx.as_mut_ptr());
+        }
+        THREAD_DEFAULT_DEPTH.with(|cell| cell.set(depth - 1));
+    }
+}
+
 #[repr(C)]
 pub struct Source<Callback = SourceCallback> {
     raw: ffi::GSource,
@@ -172,10 +475,10 @@ pub struct AttachedSource<Callback> {
     phantom_data: marker::PhantomData<Callback>
 }
 
-unsafe impl<C> Send for Source<C> where C: Into<RawCallback> { }
+unsafe impl<C> Send for Source<C> where C: Into<RawCallback> + Send { }
 
-unsafe impl<C> Send for AttachedSource<C> where C: Into<RawCallback> { }
-unsafe impl<C> Sync for AttachedSource<C> where C: Into<RawCallback> { }
+unsafe impl<C> Send for AttachedSource<C> where C: Into<RawCallback> + Send { }
+unsafe impl<C> Sync for AttachedSource<C> where C: Into<RawCallback> + Send { }
 
 macro_rules! common_source_impls {
     ($name:ident) => {
@@ -198,8 +501,8 @@ common_source_impls!(Source);
 common_source_impls!(AttachedSource);
 
 impl<C> Source<C> where C: Into<RawCallback> {
-    pub fn set_callback(&self, callback: C)
-    {
+
+    fn set_raw_callback(&self, callback: C) {
         let raw: RawCallback = callback.into();
         unsafe {
             ffi::g_source_set_callback(self.as_mut_ptr(),
@@ -208,24 +511,138 @@ impl<C> Source<C> where C: Into<RawCallback> {
         mem::forget(raw);
     }
 
+    fn get_context(&self) -> Option<&MainContext> {
+        unsafe {
+            let ctx = ffi::g_source_get_context(self.as_mut_ptr());
+            if ctx.is_null() {
+                return None;
+            }
+            Some(wrap::from_raw(ctx))
+        }
+    }
+
     pub fn set_priority(&self, priority: gint) {
         unsafe {
             ffi::g_source_set_priority(self.as_mut_ptr(), priority);
         }
     }
+
+    pub fn priority(&self) -> gint {
+        unsafe { ffi::g_source_get_priority(self.as_mut_ptr()) }
+    }
+
+    pub fn set_name(&self, name: &CStr) {
+        unsafe {
+            ffi::g_source_set_name(self.as_mut_ptr(), name.as_ptr());
+        }
+    }
+
+    // The name is copied, because the string owned by the source
+    // is freed when the name is changed
+    pub fn name(&self) -> Option<CString> {
+        unsafe {
+            let name = ffi::g_source_get_name(self.as_mut_ptr());
+            if name.is_null() {
+                return None;
+            }
+            Some(CStr::from_ptr(name).to_owned())
+        }
+    }
+
+    pub fn set_can_recurse(&self, can_recurse: bool) {
+        unsafe {
+            ffi::g_source_set_can_recurse(self.as_mut_ptr(),
+                                          can_recurse as gboolean);
+        }
+    }
+
+    pub fn can_recurse(&self) -> bool {
+        is_true(unsafe { ffi::g_source_get_can_recurse(self.as_mut_ptr()) })
+    }
+
+    pub fn set_ready_time(&self, ready_time: Option<MonotonicTime>) {
+        let raw = match ready_time {
+            Some(t) => t.0,
+            None    => -1
+        };
+        unsafe {
+            ffi::g_source_set_ready_time(self.as_mut_ptr(), raw);
+        }
+    }
+
+    pub fn ready_time(&self) -> Option<MonotonicTime> {
+        let raw = unsafe { ffi::g_source_get_ready_time(self.as_mut_ptr()) };
+        if raw < 0 {
+            None
+        } else {
+            Some(MonotonicTime(raw))
+        }
+    }
+
+    pub fn add_child_source(&self, child: &Source<C>) {
+        unsafe {
+            ffi::g_source_add_child_source(self.as_mut_ptr(),
+                                           child.as_mut_ptr());
+        }
+    }
+
+    pub fn remove_child_source(&self, child: &Source<C>) {
+        unsafe {
+            ffi::g_source_remove_child_source(self.as_mut_ptr(),
+                                              child.as_mut_ptr());
+        }
+    }
+}
+
+impl Source<SourceCallback> {
+    pub fn set_callback(&self, callback: SourceCallback) {
+        self.set_raw_callback(callback);
+    }
 }
 
-impl<C> Ref<Source<C>> {
-    pub fn attach(self, ctx: &MainContext) -> Ref<AttachedSource<C>> {
+impl Source<LocalSourceCallback> {
+    pub fn set_callback(&self, callback: LocalSourceCallback) {
+        if let Some(ctx) = self.get_context() {
+            assert_local_context(ctx);
+        }
+        self.set_raw_callback(callback);
+    }
+}
+
+unsafe fn attach_source<C>(source: Ref<Source<C>>, ctx: &MainContext)
+                          -> Ref<AttachedSource<C>>
+{
+    let source_ptr = source.as_mut_ptr();
+    ffi::g_source_attach(source_ptr, ctx.as_mut_ptr());
+    mem::forget(source);
+    Ref::from_raw(source_ptr)
+}
+
+impl Ref<Source<SourceCallback>> {
+    pub fn attach(self, ctx: &MainContext)
+                 -> Ref<AttachedSource<SourceCallback>>
+    {
+        unsafe { attach_source(self, ctx) }
+    }
+
+    pub fn into_local(self) -> Ref<Source<LocalSourceCallback>> {
         unsafe {
             let source_ptr = self.as_mut_ptr();
-            ffi::g_source_attach(source_ptr, ctx.as_mut_ptr());
             mem::forget(self);
             Ref::from_raw(source_ptr)
         }
     }
 }
 
+impl Ref<Source<LocalSourceCallback>> {
+    pub fn attach(self, ctx: &MainContext)
+                 -> Ref<AttachedSource<LocalSourceCallback>>
+    {
+        assert_local_context(ctx);
+        unsafe { attach_source(self, ctx) }
+    }
+}
+
 impl<C> AttachedSource<C> {
     #[inline]
     pub fn as_source(&self) -> &Source<C> {
@@ -235,6 +652,18 @@ impl<C> AttachedSource<C> {
     pub fn destroy(&self) {
         unsafe { ffi::g_source_destroy(self.as_mut_ptr()) }
     }
+
+    pub fn is_destroyed(&self) -> bool {
+        is_true(unsafe { ffi::g_source_is_destroyed(self.as_mut_ptr()) })
+    }
+
+    pub fn id(&self) -> guint {
+        unsafe { ffi::g_source_get_id(self.as_mut_ptr()) }
+    }
+
+    pub fn get_time(&self) -> MonotonicTime {
+        MonotonicTime(unsafe { ffi::g_source_get_time(self.as_mut_ptr()) })
+    }
 }
 
 impl<C> convert::AsRef<Source<C>> for AttachedSource<C> {
@@ -252,6 +681,10 @@ pub fn idle_source_new() -> Ref<Source> {
 }
 
 pub fn timeout_source_new(interval: guint) -> Ref<Source> {
+    let duration = Duration::from_millis(interval as u64);
+    if let Some(source) = testclock::installed_timeout_source_new(duration) {
+        return source;
+    }
     unsafe {
         let source = ffi::g_timeout_source_new(interval);
         Ref::from_raw(source)
@@ -259,12 +692,125 @@ pub fn timeout_source_new(interval: guint) -> Ref<Source> {
 }
 
 pub fn timeout_source_new_seconds(interval: guint) -> Ref<Source> {
+    let duration = Duration::from_secs(interval as u64);
+    if let Some(source) = testclock::installed_timeout_source_new(duration) {
+        return source;
+    }
     unsafe {
         let source = ffi::g_timeout_source_new_seconds(interval);
         Ref::from_raw(source)
     }
 }
 
+// Uses the coarser, more power-efficient seconds timeout
+// when the interval is a whole number of seconds.
+// Sub-millisecond intervals are rounded up to whole milliseconds.
+pub fn timeout_source_from_duration(interval: Duration) -> Ref<Source> {
+    let secs = interval.as_secs();
+    let nanos = interval.subsec_nanos();
+    if nanos == 0 && secs != 0 {
+        assert!(secs <= guint::max_value() as u64,
+                "timeout interval is too long: {} s", secs);
+        timeout_source_new_seconds(secs as guint)
+    } else {
+        let millis = secs.checked_mul(1000).and_then(|ms| {
+            ms.checked_add(((nanos + 999_999) / 1_000_000) as u64)
+        });
+        match millis {
+            Some(ms) if ms <= guint::max_value() as u64 => {
+                timeout_source_new(ms as guint)
+            }
+            _ => panic!("timeout interval is too long: {:?}", interval)
+        }
+    }
+}
+
+// Time as returned by g_get_monotonic_time() and g_source_get_time(),
+// in microseconds
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct MonotonicTime(i64);
+
+fn duration_to_micros(d: Duration) -> i64 {
+    let micros = d.as_secs().checked_mul(1_000_000).and_then(|us| {
+        us.checked_add((d.subsec_nanos() / 1000) as u64)
+    });
+    match micros {
+        Some(us) if us <= i64::max_value() as u64 => us as i64,
+        _ => panic!("duration is out of range: {:?}", d)
+    }
+}
+
+fn micros_to_duration(us: i64) -> Duration {
+    debug_assert!(us >= 0);
+    Duration::new((us / 1_000_000) as u64, ((us % 1_000_000) * 1000) as u32)
+}
+
+impl MonotonicTime {
+
+    #[inline]
+    pub fn from_micros(us: i64) -> MonotonicTime {
+        MonotonicTime(us)
+    }
+
+    #[inline]
+    pub fn as_micros(&self) -> i64 {
+        self.0
+    }
+
+    // Returns zero duration if `earlier` is later than self
+    pub fn duration_since(&self, earlier: MonotonicTime) -> Duration {
+        if earlier.0 >= self.0 {
+            return Duration::new(0, 0);
+        }
+        micros_to_duration(self.0 - earlier.0)
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        monotonic_time().duration_since(*self)
+    }
+}
+
+impl Add<Duration> for MonotonicTime {
+    type Output = MonotonicTime;
+
+    fn add(self, rhs: Duration) -> MonotonicTime {
+        let t = self.0.checked_add(duration_to_micros(rhs))
+                      .expect("overflow when adding duration to time");
+        MonotonicTime(t)
+    }
+}
+
+impl Sub<Duration> for MonotonicTime {
+    type Output = MonotonicTime;
+
+    fn sub(self, rhs: Duration) -> MonotonicTime {
+        let t = self.0.checked_sub(duration_to_micros(rhs))
+                      .expect("overflow when subtracting duration from time");
+        MonotonicTime(t)
+    }
+}
+
+impl Sub<MonotonicTime> for MonotonicTime {
+    type Output = Duration;
+
+    fn sub(self, rhs: MonotonicTime) -> Duration {
+        self.duration_since(rhs)
+    }
+}
+
+pub fn monotonic_time() -> MonotonicTime {
+    MonotonicTime(unsafe { ffi::g_get_monotonic_time() })
+}
+
+pub fn real_time() -> SystemTime {
+    let us = unsafe { ffi::g_get_real_time() };
+    if us >= 0 {
+        UNIX_EPOCH + micros_to_duration(us)
+    } else {
+        UNIX_EPOCH - micros_to_duration(-us)
+    }
+}
+
 #[repr(C)]
 pub struct MainLoop {
     raw: ffi::GMainLoop
@@ -291,16 +837,76 @@ impl LoopRunner {
         }
     }
 
-    pub fn run_after<F>(&self, setup: F) where F: FnOnce(Ref<MainLoop>) {
+    pub fn get_context(&self) -> &MainContext {
         unsafe {
             let ctx = ffi::g_main_loop_get_context(self.mainloop);
-            ffi::g_main_context_push_thread_default(ctx);
+            wrap::from_raw(ctx)
+        }
+    }
+
+    pub fn run_after<F>(&self, setup: F) where F: FnOnce(Ref<MainLoop>) {
+        unsafe {
+            let ctx: &MainContext = wrap::from_raw(
+                    ffi::g_main_loop_get_context(self.mainloop));
+            let _thread_default = ctx.push_thread_default();
+
+            // Acquire the context before setup, so that sources
+            // with thread-local callbacks can be attached to it
+            let _owner = ctx.acquire()
+                            .expect("failed to acquire the loop's context");
 
             setup(Ref::new(wrap::from_raw(self.mainloop)));
 
-            ffi::g_main_loop_run(self.mainloop);
+            run_loop(self.mainloop);
+        }
+    }
 
-            ffi::g_main_context_pop_thread_default(ctx);
+    pub fn run_until<T, F>(&self, setup: F) -> T
+        where F: FnOnce(LoopQuitter<T>)
+    {
+        let result = Arc::new(Mutex::new(None));
+        self.run_after(|mainloop| {
+            setup(LoopQuitter { mainloop: mainloop, result: result.clone() });
+        });
+        let res = result.lock().unwrap().take();
+        res.expect("main loop quit without a result")
+    }
+}
+
+pub struct LoopQuitter<T> {
+    mainloop: Ref<MainLoop>,
+    result: Arc<Mutex<Option<T>>>
+}
+
+impl<T> LoopQuitter<T> {
+
+    #[inline]
+    pub fn main_loop(&self) -> &MainLoop {
+        &self.mainloop
+    }
+
+    #[inline]
+    pub fn get_context(&self) -> &MainContext {
+        self.mainloop.get_context()
+    }
+
+    pub fn quit(&self, result: T) {
+        {
+            let mut slot = self.result.lock().unwrap();
+            // The first result to arrive is returned from run_until
+            if slot.is_none() {
+                *slot = Some(result);
+            }
+        }
+        self.mainloop.quit();
+    }
+}
+
+impl<T> Clone for LoopQuitter<T> {
+    fn clone(&self) -> LoopQuitter<T> {
+        LoopQuitter {
+            mainloop: self.mainloop.clone(),
+            result: self.result.clone()
         }
     }
 }
@@ -315,6 +921,28 @@ impl Drop for LoopRunner {
 
 impl MainLoop {
 
+    pub fn new(ctx: Option<&MainContext>, is_running: bool) -> Ref<MainLoop> {
+        unsafe {
+            let ctx_ptr = match ctx {
+                Some(ctx) => ctx.as_mut_ptr(),
+                None      => ptr::null_mut()
+            };
+            let mainloop = ffi::g_main_loop_new(ctx_ptr,
+                                                is_running as gboolean);
+            Ref::from_raw(mainloop)
+        }
+    }
+
+    pub fn run(&self) {
+        unsafe {
+            run_loop(self.as_mut_ptr());
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        is_true(unsafe { ffi::g_main_loop_is_running(self.as_mut_ptr()) })
+    }
+
     pub fn get_context(&self) -> &MainContext {
         unsafe {
             let ctx = ffi::g_main_loop_get_context(self.as_mut_ptr());
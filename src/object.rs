@@ -17,13 +17,21 @@
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 
 use gobject as ffi;
+use glib;
 use gtype;
 use gtype::GType;
+use panic;
+use quark::Quark;
 use refcount::Refcount;
 use types::gpointer;
+use util::{into_destroy_notify, is_true};
+use util::{tagged_box_free, tagged_box_into_pointer, tagged_box_is};
+use util::{tagged_box_ref, tagged_box_take};
 use wrap::Wrapper;
 
+use std::ffi::CString;
 use std::mem::transmute;
+use std::ptr;
 
 pub unsafe trait ObjectType {
     fn get_type() -> GType;
@@ -74,3 +82,117 @@ pub fn cast<T, U>(source: &T) -> &U
     assert_instance_of::<T, U>(source);
     unsafe { transmute(source) }
 }
+
+fn object_ptr<O>(object: &O) -> *mut ffi::GObject
+    where O: ObjectType + Wrapper
+{
+    unsafe { object.as_mut_ptr() as *mut ffi::GObject }
+}
+
+// Data set through this API is stored under a private quark derived
+// from the one given, so the type tag is only ever read from values
+// stored by set_qdata(), not from pointers stored by other code.
+fn qdata_key(quark: Quark) -> Quark {
+    let mut name = b"grust-qdata:".to_vec();
+    name.extend_from_slice(quark.to_bytes());
+    let name = CString::new(name).unwrap();
+    unsafe { Quark::from_raw(glib::g_quark_from_string(name.as_ptr())) }
+}
+
+// Replaces any data previously set for the quark, dropping it.
+// The object can be shared between threads and the data gets dropped
+// on the thread that releases the last reference, so the data type
+// must be both Send and Sync.
+pub fn set_qdata<O, T>(object: &O, quark: Quark, data: T)
+    where O: ObjectType + Wrapper, T: Send + Sync + 'static
+{
+    unsafe {
+        ffi::g_object_set_qdata_full(object_ptr(object),
+                qdata_key(quark).to_raw(),
+                tagged_box_into_pointer(data),
+                Some(into_destroy_notify(tagged_box_free::<T>)));
+    }
+}
+
+unsafe extern "C" fn qdata_clone<T>(data: gpointer, user_data: gpointer)
+                                   -> gpointer
+    where T: Clone + 'static
+{
+    let slot = &mut *(user_data as *mut Option<T>);
+    if let Some(value) = tagged_box_ref::<T>(data) {
+        // Called with the object's data lock held
+        *slot = Some(panic::catch_or_abort(|| value.clone()));
+    }
+    ptr::null_mut()
+}
+
+unsafe fn into_duplicate_func(
+        func: unsafe extern "C" fn(gpointer, gpointer) -> gpointer)
+        -> glib::GDuplicateFunc
+{
+    transmute(func)
+}
+
+// Returns a clone of the data, or None if no data is set
+// or it has a different type. The data is cloned while it is
+// protected from being replaced or removed by other threads.
+pub fn qdata<O, T>(object: &O, quark: Quark) -> Option<T>
+    where O: ObjectType + Wrapper, T: Clone + Send + Sync + 'static
+{
+    let mut slot: Option<T> = None;
+    unsafe {
+        ffi::g_object_dup_qdata(object_ptr(object),
+                qdata_key(quark).to_raw(),
+                Some(into_duplicate_func(qdata_clone::<T>)),
+                &mut slot as *mut Option<T> as gpointer);
+    }
+    slot
+}
+
+// Takes the data out of the object if it has the requested type,
+// otherwise leaves it in place and returns None.
+pub fn steal_qdata<O, T>(object: &O, quark: Quark) -> Option<T>
+    where O: ObjectType + Wrapper, T: Send + Sync + 'static
+{
+    let obj = object_ptr(object);
+    let key = qdata_key(quark).to_raw();
+    unsafe {
+        // The pointer is not dereferenced until the data is taken
+        // from the object, as it may be freed concurrently
+        let p = ffi::g_object_get_qdata(obj, key);
+        if p.is_null() {
+            return None;
+        }
+        let mut destroy: Option<glib::GDestroyNotify> = None;
+        let taken = ffi::g_object_replace_qdata(obj, key,
+                                                p, ptr::null_mut(),
+                                                None, &mut destroy);
+        if !is_true(taken) {
+            return None;
+        }
+        if tagged_box_is::<T>(p) {
+            return Some(tagged_box_take(p));
+        }
+        // Put back the data of another type, unless something
+        // has been set in the meantime
+        let restored = ffi::g_object_replace_qdata(obj, key,
+                                                   ptr::null_mut(), p,
+                                                   destroy, ptr::null_mut());
+        if !is_true(restored) {
+            if let Some(destroy) = destroy {
+                destroy(p);
+            }
+        }
+        None
+    }
+}
+
+pub fn remove_qdata<O>(object: &O, quark: Quark)
+    where O: ObjectType + Wrapper
+{
+    unsafe {
+        ffi::g_object_set_qdata(object_ptr(object),
+                                qdata_key(quark).to_raw(),
+                                ptr::null_mut());
+    }
+}
@@ -0,0 +1,147 @@
+// This file is part of Grust, GObject introspection bindings for Rust
+//
+// Copyright (C) 2015  Mikhail Zabaluev <mikhail.zabaluev@gmail.com>
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; either
+// version 2.1 of the License, or (at your option) any later version.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+
+//! Handling of panics in Rust code called back from C.
+//!
+//! Unwinding into C frames is undefined behaviour, so every
+//! `extern "C"` trampoline in this crate catches panics and
+//! deals with them according to the process-wide `Policy`.
+//! A panic can only be resumed if the callback has been dispatched
+//! from a resume point set up by this crate, such as running
+//! a main loop; otherwise, the process is aborted.
+
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::io;
+use std::io::Write;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::process;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::Relaxed;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Policy {
+    // Print a message and abort the process
+    Abort,
+    // Save the panic payload and resume unwinding on the same thread
+    // once control returns from the main loop
+    Resume
+}
+
+const POLICY_ABORT  : usize = 0;
+const POLICY_RESUME : usize = 1;
+
+static POLICY: AtomicUsize = AtomicUsize::new(POLICY_ABORT);
+
+thread_local!(static PENDING: RefCell<Option<Box<Any + Send>>>
+              = RefCell::new(None));
+
+thread_local!(static RESUME_DEPTH: Cell<usize> = Cell::new(0));
+
+pub fn set_policy(policy: Policy) {
+    let val = match policy {
+        Policy::Abort  => POLICY_ABORT,
+        Policy::Resume => POLICY_RESUME
+    };
+    POLICY.store(val, Relaxed);
+}
+
+pub fn policy() -> Policy {
+    match POLICY.load(Relaxed) {
+        POLICY_ABORT => Policy::Abort,
+        _            => Policy::Resume
+    }
+}
+
+pub fn catch<F, R>(f: F) -> Option<R> where F: FnOnce() -> R {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(res) => Some(res),
+        Err(payload) => {
+            // With no resume point on this thread's stack,
+            // the panic would be resumed at some unrelated place,
+            // or never at all
+            if policy() == Policy::Abort || !in_resume_point() {
+                abort_with(&payload);
+            }
+            PENDING.with(|cell| {
+                let mut pending = cell.borrow_mut();
+                // Only the first panic gets resumed
+                if pending.is_none() {
+                    *pending = Some(payload);
+                }
+            });
+            None
+        }
+    }
+}
+
+pub fn catch_or_abort<F, R>(f: F) -> R where F: FnOnce() -> R {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(res) => res,
+        Err(payload) => abort_with(&payload)
+    }
+}
+
+struct ResumePointGuard;
+
+impl Drop for ResumePointGuard {
+    fn drop(&mut self) {
+        RESUME_DEPTH.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
+// Calls into C code that may dispatch callbacks, and resumes
+// a panic caught in any of them once the call has returned.
+pub fn resume_point<F, R>(f: F) -> R where F: FnOnce() -> R {
+    let res = {
+        RESUME_DEPTH.with(|depth| depth.set(depth.get() + 1));
+        let _guard = ResumePointGuard;
+        f()
+    };
+    resume_pending();
+    res
+}
+
+fn in_resume_point() -> bool {
+    RESUME_DEPTH.with(|depth| depth.get() != 0)
+}
+
+pub fn is_pending() -> bool {
+    PENDING.with(|cell| cell.borrow().is_some())
+}
+
+pub fn resume_pending() {
+    let pending = PENDING.with(|cell| cell.borrow_mut().take());
+    if let Some(payload) = pending {
+        resume_unwind(payload);
+    }
+}
+
+fn abort_with(payload: &Box<Any + Send>) -> ! {
+    let msg = match payload.downcast_ref::<&'static str>() {
+        Some(s) => *s,
+        None => match payload.downcast_ref::<String>() {
+            Some(s) => &s[..],
+            None => "Box<Any>"
+        }
+    };
+    let _ = writeln!(io::stderr(),
+                     "panic in a callback called from C: '{}'; aborting",
+                     msg);
+    process::abort()
+}
@@ -16,19 +16,31 @@
 // License along with this library; if not, write to the Free Software
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 
+use gstr::Utf8;
 use types::gchar;
 use util::escape_bytestring;
 
 use glib as ffi;
 
-use std::ffi::CStr;
+use std::ffi::{CStr, CString, NulError};
 use std::fmt;
+use std::str::FromStr;
 use std::sync::atomic;
 
-#[derive(Copy, Clone, Eq, PartialEq)]
+pub mod raw {
+    // Support macro g_extended_error_domain! by reexporting GQuark
+    pub type GQuark = ::glib::GQuark;
+}
+
+// Quarks are ordered by their integer values, in the order
+// the strings were interned
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct Quark(ffi::GQuark);
 
-pub struct StaticQuark(pub &'static [u8], pub atomic::AtomicUsize);
+pub struct StaticQuark {
+    s: &'static str,
+    cached: atomic::AtomicUsize
+}
 
 impl Quark {
 
@@ -53,6 +65,16 @@ impl Quark {
         unsafe { Quark::from_static_internal(bytes) }
     }
 
+    // Looks up the quark without interning the string
+    pub fn try_from_str(s: &str) -> Option<Quark> {
+        let c_str = match CString::new(s) {
+            Ok(c_str) => c_str,
+            Err(_) => return None
+        };
+        let q = unsafe { ffi::g_quark_try_string(c_str.as_ptr()) };
+        if q == 0 { None } else { Some(Quark(q)) }
+    }
+
     unsafe fn from_static_internal(s: &'static [u8]) -> Quark {
         let p = s.as_ptr() as *const gchar;
         let q = ffi::g_quark_from_static_string(p);
@@ -78,6 +100,16 @@ impl Quark {
     }
 }
 
+impl FromStr for Quark {
+    type Err = NulError;
+
+    fn from_str(s: &str) -> Result<Quark, NulError> {
+        let c_str = try!(CString::new(s));
+        let q = unsafe { ffi::g_quark_from_string(c_str.as_ptr()) };
+        Ok(Quark(q))
+    }
+}
+
 impl fmt::Display for Quark {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", String::from_utf8_lossy(self.to_bytes()))
@@ -92,15 +124,46 @@ impl fmt::Debug for Quark {
 
 impl StaticQuark {
 
+    // Used in static initializers, so the checks fail the build
+    // rather than panic at run time.
+    pub const fn new(s: &'static str) -> StaticQuark {
+        let bytes = s.as_bytes();
+        assert!(!bytes.is_empty() && bytes[bytes.len() - 1] == 0,
+                "static quark string is not null-terminated");
+        let mut i = 0;
+        while i < bytes.len() - 1 {
+            assert!(bytes[i] != 0,
+                    "static quark string contains an interior NUL");
+            i += 1;
+        }
+        StaticQuark { s: s, cached: atomic::AtomicUsize::new(0) }
+    }
+
     pub fn get(&self) -> Quark {
-        let StaticQuark(s, ref cached) = *self;
-        let q = cached.load(atomic::Ordering::Relaxed) as ffi::GQuark;
+        let q = self.cached.load(atomic::Ordering::Acquire) as ffi::GQuark;
         if q != 0 {
             Quark(q)
         } else {
-            let quark = Quark::from_static_bytes(s);
-            cached.store(quark.to_raw() as usize, atomic::Ordering::Relaxed);
+            // Racing threads get the same quark, so no further
+            // synchronization is needed
+            let quark = unsafe { Quark::from_static_internal(self.s.as_bytes()) };
+            self.cached.store(quark.to_raw() as usize, atomic::Ordering::Release);
             quark
         }
     }
 }
+
+pub fn intern_string(s: &str) -> Result<&'static Utf8, NulError> {
+    let c_str = try!(CString::new(s));
+    unsafe {
+        let p = ffi::g_intern_string(c_str.as_ptr());
+        Ok(Utf8::from_ptr(p))
+    }
+}
+
+pub fn intern_static_string(s: &'static Utf8) -> &'static Utf8 {
+    unsafe {
+        let p = ffi::g_intern_static_string(s.as_ptr());
+        Utf8::from_ptr(p)
+    }
+}
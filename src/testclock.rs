@@ -0,0 +1,354 @@
+// This file is part of Grust, GObject introspection bindings for Rust
+//
+// Copyright (C) 2015  Mikhail Zabaluev <mikhail.zabaluev@gmail.com>
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; either
+// version 2.1 of the License, or (at your option) any later version.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+
+//! Virtual time for deterministic testing of timeouts.
+//!
+//! GLib's own timeout sources always run on the real monotonic clock.
+//! While a `TestClock` exists on a thread, timeout sources created
+//! on that thread with the functions in module `mainloop` are
+//! custom sources that expire by the virtual time of the clock.
+//! The time only moves when `advance()` is called, or, if automatic
+//! advance is enabled, when the main context would otherwise block
+//! waiting for a virtual timeout to expire.
+
+use mainloop;
+use mainloop::{MainContext, MonotonicTime, Source};
+use refcount::Ref;
+use types::{FALSE, TRUE};
+use types::{gboolean, gint, gpointer, guint};
+use wrap::Wrapper;
+
+use glib as ffi;
+
+use std::cell::RefCell;
+use std::marker;
+use std::mem;
+use std::ptr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+struct ClockState {
+    now: MonotonicTime,
+    auto_advance: bool
+}
+
+struct Clock {
+    state: Mutex<ClockState>,
+    // The context the clock is installed for. It is only compared
+    // with, never dereferenced, as sources may outlive the clock.
+    ctx: *mut ffi::GMainContext
+}
+
+impl Clock {
+    fn now(&self) -> MonotonicTime {
+        self.state.lock().unwrap().now
+    }
+
+    fn auto_advance(&self) -> bool {
+        self.state.lock().unwrap().auto_advance
+    }
+
+    fn advance_to(&self, t: MonotonicTime) {
+        let mut state = self.state.lock().unwrap();
+        if t > state.now {
+            state.now = t;
+        }
+    }
+}
+
+thread_local!(static INSTALLED: RefCell<Vec<Arc<Clock>>>
+              = RefCell::new(Vec::new()));
+
+// The earliest virtual deadline found in the prepare stage of
+// the current main context iteration on this thread
+thread_local!(static NEXT_DEADLINE: RefCell<Option<(Arc<Clock>, MonotonicTime)>>
+              = RefCell::new(None));
+
+#[repr(C)]
+struct VirtualTimeout {
+    source: ffi::GSource,
+    clock: *const Clock,
+    interval: Duration,
+    expiration: MonotonicTime,
+    // Expiration by the real clock, set once the source is found
+    // to be attached to a context the virtual clock is not installed for
+    real_expiration: Option<MonotonicTime>
+}
+
+// Mirrors the layout of GSourceFuncs
+#[repr(C)]
+struct SourceFuncs {
+    prepare: unsafe extern "C" fn(*mut ffi::GSource, *mut gint) -> gboolean,
+    check: unsafe extern "C" fn(*mut ffi::GSource) -> gboolean,
+    dispatch: unsafe extern "C" fn(*mut ffi::GSource,
+                                   Option<unsafe extern "C" fn(gpointer) -> gboolean>,
+                                   gpointer)
+                                   -> gboolean,
+    finalize: unsafe extern "C" fn(*mut ffi::GSource),
+    closure_callback: Option<unsafe extern "C" fn()>,
+    closure_marshal: Option<unsafe extern "C" fn()>
+}
+
+static VIRTUAL_TIMEOUT_FUNCS: SourceFuncs = SourceFuncs {
+    prepare: virtual_timeout_prepare,
+    check: virtual_timeout_check,
+    dispatch: virtual_timeout_dispatch,
+    finalize: virtual_timeout_finalize,
+    closure_callback: None,
+    closure_marshal: None
+};
+
+unsafe fn virtual_timeout<'a>(source: *mut ffi::GSource)
+                             -> &'a mut VirtualTimeout
+{
+    &mut *(source as *mut VirtualTimeout)
+}
+
+unsafe fn in_clock_context(source: *mut ffi::GSource) -> bool {
+    let vt = virtual_timeout(source);
+    ffi::g_source_get_context(source) == (*vt.clock).ctx
+}
+
+unsafe fn real_expiration(source: *mut ffi::GSource) -> MonotonicTime {
+    let vt = virtual_timeout(source);
+    match vt.real_expiration {
+        Some(t) => t,
+        None => {
+            let t = real_source_time(source) + vt.interval;
+            vt.real_expiration = Some(t);
+            t
+        }
+    }
+}
+
+unsafe fn real_source_time(source: *mut ffi::GSource) -> MonotonicTime {
+    MonotonicTime::from_micros(ffi::g_source_get_time(source))
+}
+
+unsafe extern "C" fn virtual_timeout_prepare(source: *mut ffi::GSource,
+                                             timeout: *mut gint)
+                                             -> gboolean
+{
+    if !in_clock_context(source) {
+        // Behave like a real timeout in other contexts
+        let now = real_source_time(source);
+        let expiration = real_expiration(source);
+        if now >= expiration {
+            *timeout = 0;
+            return TRUE;
+        }
+        let remaining = expiration.as_micros() - now.as_micros();
+        let millis = (remaining + 999) / 1000;
+        *timeout = if millis > gint::max_value() as i64 {
+            gint::max_value()
+        } else {
+            millis as gint
+        };
+        return FALSE;
+    }
+    let vt = virtual_timeout(source);
+    let clock = &*vt.clock;
+    if clock.now() >= vt.expiration {
+        *timeout = 0;
+        return TRUE;
+    }
+    // Wait for the clock to be advanced
+    *timeout = -1;
+    NEXT_DEADLINE.with(|cell| {
+        let mut next = cell.borrow_mut();
+        let earlier = match *next {
+            Some((_, deadline)) => vt.expiration < deadline,
+            None => true
+        };
+        if earlier {
+            let clock_ref = Arc::from_raw(vt.clock);
+            *next = Some((clock_ref.clone(), vt.expiration));
+            mem::forget(clock_ref);
+        }
+    });
+    FALSE
+}
+
+unsafe extern "C" fn virtual_timeout_check(source: *mut ffi::GSource)
+                                           -> gboolean
+{
+    if !in_clock_context(source) {
+        return (real_source_time(source) >= real_expiration(source))
+               as gboolean;
+    }
+    let vt = virtual_timeout(source);
+    let clock = &*vt.clock;
+    (clock.now() >= vt.expiration) as gboolean
+}
+
+unsafe extern "C" fn virtual_timeout_dispatch(
+        source: *mut ffi::GSource,
+        callback: Option<unsafe extern "C" fn(gpointer) -> gboolean>,
+        user_data: gpointer)
+        -> gboolean
+{
+    let callback = match callback {
+        Some(func) => func,
+        None => return FALSE
+    };
+    let res = callback(user_data);
+    if res != FALSE {
+        if in_clock_context(source) {
+            let vt = virtual_timeout(source);
+            let clock = &*vt.clock;
+            vt.expiration = clock.now() + vt.interval;
+        } else {
+            let vt = virtual_timeout(source);
+            vt.real_expiration = Some(real_source_time(source) + vt.interval);
+        }
+    }
+    res
+}
+
+unsafe extern "C" fn virtual_timeout_finalize(source: *mut ffi::GSource) {
+    let vt = virtual_timeout(source);
+    let clock: Arc<Clock> = Arc::from_raw(vt.clock);
+    mem::drop(clock);
+}
+
+fn virtual_timeout_new(clock: &Arc<Clock>, interval: Duration) -> Ref<Source> {
+    unsafe {
+        let funcs = &VIRTUAL_TIMEOUT_FUNCS as *const SourceFuncs;
+        let source = ffi::g_source_new(funcs as *mut ffi::GSourceFuncs,
+                                       mem::size_of::<VirtualTimeout>() as guint);
+        let vt = virtual_timeout(source);
+        vt.clock = Arc::into_raw(clock.clone());
+        vt.interval = interval;
+        vt.expiration = clock.now() + interval;
+        ptr::write(&mut vt.real_expiration, None);
+        Ref::from_raw(source)
+    }
+}
+
+unsafe extern "C" fn virtual_poll(fds: *mut ffi::GPollFD, nfds: guint,
+                                  timeout: gint)
+                                  -> gint
+{
+    let next = NEXT_DEADLINE.with(|cell| cell.borrow_mut().take());
+    if let Some((clock, deadline)) = next {
+        if timeout != 0 && clock.auto_advance() {
+            let ready = ffi::g_poll(fds, nfds, 0);
+            if ready != 0 {
+                return ready;
+            }
+            clock.advance_to(deadline);
+            return 0;
+        }
+    }
+    ffi::g_poll(fds, nfds, timeout)
+}
+
+unsafe fn into_poll_func(func: unsafe extern "C" fn(*mut ffi::GPollFD,
+                                                     guint, gint)
+                                                     -> gint)
+                        -> ffi::GPollFunc
+{
+    mem::transmute(func)
+}
+
+// Used by the timeout source constructors in module mainloop
+pub fn installed_timeout_source_new(interval: Duration)
+                                   -> Option<Ref<Source>>
+{
+    INSTALLED.with(|installed| {
+        installed.borrow().last().map(|clock| {
+            virtual_timeout_new(clock, interval)
+        })
+    })
+}
+
+pub struct TestClock {
+    clock: Arc<Clock>,
+    ctx: Ref<MainContext>,
+    prev_poll: ffi::GPollFunc,
+    not_send: marker::PhantomData<*mut ()>
+}
+
+impl TestClock {
+
+    pub fn new(ctx: &MainContext) -> TestClock {
+        let clock = Arc::new(Clock {
+            state: Mutex::new(ClockState {
+                now: mainloop::monotonic_time(),
+                auto_advance: false
+            }),
+            ctx: ctx.as_mut_ptr()
+        });
+        let prev_poll = unsafe {
+            let prev = ffi::g_main_context_get_poll_func(ctx.as_mut_ptr());
+            ffi::g_main_context_set_poll_func(ctx.as_mut_ptr(),
+                                              into_poll_func(virtual_poll));
+            prev
+        };
+        INSTALLED.with(|installed| {
+            installed.borrow_mut().push(clock.clone());
+        });
+        TestClock {
+            clock: clock,
+            ctx: Ref::new(ctx),
+            prev_poll: prev_poll,
+            not_send: marker::PhantomData
+        }
+    }
+
+    pub fn now(&self) -> MonotonicTime {
+        self.clock.now()
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        {
+            let mut state = self.clock.state.lock().unwrap();
+            state.now = state.now + duration;
+        }
+        self.ctx.wakeup();
+    }
+
+    pub fn set_auto_advance(&self, auto_advance: bool) {
+        self.clock.state.lock().unwrap().auto_advance = auto_advance;
+        self.ctx.wakeup();
+    }
+
+    pub fn timeout_source_new(&self, interval: Duration) -> Ref<Source> {
+        virtual_timeout_new(&self.clock, interval)
+    }
+}
+
+impl Drop for TestClock {
+    fn drop(&mut self) {
+        INSTALLED.with(|installed| {
+            let mut installed = installed.borrow_mut();
+            let pos = installed.iter().rposition(|c| {
+                &**c as *const Clock == &*self.clock as *const Clock
+            });
+            if let Some(pos) = pos {
+                installed.remove(pos);
+            }
+        });
+        // Restore the poll function that was in effect before,
+        // which may be the virtual poll of an enclosing clock
+        unsafe {
+            ffi::g_main_context_set_poll_func(self.ctx.as_mut_ptr(),
+                                              self.prev_poll);
+        }
+    }
+}
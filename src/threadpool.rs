@@ -0,0 +1,213 @@
+// This file is part of Grust, GObject introspection bindings for Rust
+//
+// Copyright (C) 2015  Mikhail Zabaluev <mikhail.zabaluev@gmail.com>
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; either
+// version 2.1 of the License, or (at your option) any later version.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+
+use error;
+use error::Error;
+use mainloop;
+use mainloop::{MainContext, SourceCallback};
+use panic;
+use refcount::Ref;
+use types::{FALSE, TRUE};
+use types::{gboolean, gint, gpointer, guint};
+use util::{box_from_pointer, box_into_pointer};
+
+use glib as ffi;
+
+use std::marker;
+use std::mem;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+
+type PoolFunc<T> = Box<Fn(T) + Send + Sync>;
+
+pub struct ThreadPool<T> {
+    raw: *mut ffi::GThreadPool,
+    func: *mut PoolFunc<T>,
+    exclusive: bool,
+    phantom_data: marker::PhantomData<T>
+}
+
+unsafe impl<T> Send for ThreadPool<T> where T: Send { }
+unsafe impl<T> Sync for ThreadPool<T> where T: Send { }
+
+unsafe fn into_g_func(func: unsafe extern "C" fn(gpointer, gpointer))
+                     -> ffi::GFunc
+{
+    mem::transmute(func)
+}
+
+unsafe extern "C" fn pool_func<T>(data: gpointer, user_data: gpointer) {
+    let func = &*(user_data as *const PoolFunc<T>);
+    let item: Box<T> = box_from_pointer(data);
+    // There is nowhere to resume a panic from a pool thread
+    panic::catch_or_abort(|| func(*item));
+}
+
+fn max_threads_to_raw(max_threads: Option<u32>) -> gint {
+    match max_threads {
+        Some(n) => {
+            assert!(n <= gint::max_value() as u32,
+                    "too many threads requested: {}", n);
+            n as gint
+        }
+        None => -1
+    }
+}
+
+impl<T> ThreadPool<T> where T: Send + 'static {
+
+    pub fn new<F>(func: F, max_threads: Option<u32>, exclusive: bool)
+                 -> Result<ThreadPool<T>, Error>
+        where F: Fn(T) + Send + Sync + 'static
+    {
+        // GLib fails without reporting an error in this case
+        assert!(!exclusive || max_threads.is_some(),
+                "an exclusive thread pool needs a limit on threads");
+        let func: Box<PoolFunc<T>> = Box::new(Box::new(func));
+        let func = Box::into_raw(func);
+        let res = error::call_ptr(|err| unsafe {
+            ffi::g_thread_pool_new(into_g_func(pool_func::<T>),
+                                   func as gpointer,
+                                   max_threads_to_raw(max_threads),
+                                   exclusive as gboolean,
+                                   err)
+        });
+        let raw = match res {
+            Ok(raw) => raw,
+            Err(err) => {
+                unsafe { mem::drop(Box::from_raw(func)) };
+                return Err(err);
+            }
+        };
+        Ok(ThreadPool {
+            raw: raw,
+            func: func,
+            exclusive: exclusive,
+            phantom_data: marker::PhantomData
+        })
+    }
+
+    // If an error is returned, the data is still queued and will be
+    // processed once there is a thread available.
+    pub fn push(&self, data: T) -> Result<(), Error> {
+        let data = box_into_pointer(Box::new(data));
+        error::call_bool(|err| unsafe {
+            ffi::g_thread_pool_push(self.raw, data, err)
+        })
+    }
+
+    pub fn set_max_threads(&self, max_threads: Option<u32>)
+                          -> Result<(), Error>
+    {
+        assert!(!self.exclusive || max_threads.is_some(),
+                "an exclusive thread pool needs a limit on threads");
+        let max_threads = max_threads_to_raw(max_threads);
+        error::call_bool(|err| unsafe {
+            ffi::g_thread_pool_set_max_threads(self.raw, max_threads, err)
+        })
+    }
+
+    pub fn max_threads(&self) -> Option<u32> {
+        let n = unsafe { ffi::g_thread_pool_get_max_threads(self.raw) };
+        if n < 0 {
+            None
+        } else {
+            Some(n as u32)
+        }
+    }
+
+    pub fn num_threads(&self) -> guint {
+        unsafe { ffi::g_thread_pool_get_num_threads(self.raw) }
+    }
+
+    pub fn unprocessed(&self) -> guint {
+        unsafe { ffi::g_thread_pool_unprocessed(self.raw) }
+    }
+
+    // Waits until all queued items have been processed
+    pub fn shutdown(self) {
+        mem::drop(self)
+    }
+}
+
+impl<T> Drop for ThreadPool<T> {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::g_thread_pool_free(self.raw, FALSE, TRUE);
+            // The pool threads are done with the function by now
+            mem::drop(Box::from_raw(self.func));
+        }
+    }
+}
+
+pub struct Job(Box<FnMut() + Send>);
+
+impl Job {
+    pub fn new<F>(func: F) -> Job where F: FnOnce() + Send + 'static {
+        let mut holder = Some(func);
+        Job(Box::new(move || {
+            let func = holder.take().expect("job has already run");
+            func();
+        }))
+    }
+
+    fn run(mut self) {
+        (self.0)()
+    }
+}
+
+impl ThreadPool<Job> {
+
+    pub fn for_jobs(max_threads: Option<u32>, exclusive: bool)
+                   -> Result<ThreadPool<Job>, Error>
+    {
+        ThreadPool::new(|job: Job| job.run(), max_threads, exclusive)
+    }
+
+    pub fn spawn<F>(&self, func: F) -> Result<(), Error>
+        where F: FnOnce() + Send + 'static
+    {
+        self.push(Job::new(func))
+    }
+
+    // Runs `work` in the pool and passes its result to `report`,
+    // which is dispatched by the main context `ctx`. A panic in `work`
+    // is resumed in the main context instead of calling `report`.
+    pub fn run_and_report<W, R, D>(&self, ctx: &MainContext,
+                                   work: W, report: D)
+                                  -> Result<(), Error>
+        where W: FnOnce() -> R + Send + 'static,
+              R: Send + 'static,
+              D: FnOnce(R) + Send + 'static
+    {
+        let ctx = Ref::new(ctx);
+        self.spawn(move || {
+            let res = catch_unwind(AssertUnwindSafe(work));
+            // Not using ctx.invoke(), which would call `report` right
+            // here on the pool thread if the context is not owned
+            let source = mainloop::idle_source_new();
+            source.set_priority(mainloop::PRIORITY_DEFAULT);
+            source.set_callback(SourceCallback::once(move || {
+                match res {
+                    Ok(val) => report(val),
+                    Err(payload) => resume_unwind(payload)
+                }
+            }));
+            source.attach(&ctx);
+        })
+    }
+}
@@ -16,13 +16,16 @@
 // License along with this library; if not, write to the Free Software
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 
-use types::{gboolean, gpointer, FALSE};
+use panic;
+use types::{gboolean, gchar, gpointer, FALSE};
 
 use glib;
 
+use std::any::TypeId;
 use std::ascii;
 use std::ascii::AsciiExt;
 use std::borrow::Cow;
+use std::ffi::CStr;
 use std::mem;
 use std::str;
 
@@ -45,7 +48,16 @@ pub fn escape_bytestring<'a>(s: &'a [u8]) -> Cow<'a, str> {
 
 pub unsafe extern "C" fn box_free<T>(raw: gpointer) {
     let b: Box<T> = mem::transmute(raw);
-    mem::drop(b);
+    // Destroy notifications can be called on any thread at any time,
+    // so there is no place where a panic could be resumed
+    panic::catch_or_abort(|| mem::drop(b));
+}
+
+// For strings that are expected to be valid UTF-8, such as
+// value names and nicks of enum and flags types
+pub unsafe fn str_from_ptr<'a>(p: *const gchar) -> &'a str {
+    str::from_utf8(CStr::from_ptr(p).to_bytes())
+        .expect("string is not valid UTF-8")
 }
 
 pub unsafe fn into_destroy_notify(func: unsafe extern "C" fn(gpointer))
@@ -61,3 +73,43 @@ pub unsafe fn box_from_pointer<T>(p: gpointer) -> Box<T> {
 pub fn box_into_pointer<T>(b: Box<T>) -> gpointer {
     unsafe { mem::transmute(b) }
 }
+
+// A boxed value stored in a C container that does not keep track
+// of the value type. The type ID is placed first, so it can be checked
+// through a pointer before the value type is known to match.
+#[repr(C)]
+struct Tagged<T> {
+    type_id: TypeId,
+    value: T
+}
+
+pub fn tagged_box_into_pointer<T>(value: T) -> gpointer where T: 'static {
+    let tagged = Box::new(Tagged { type_id: TypeId::of::<T>(), value: value });
+    box_into_pointer(tagged)
+}
+
+pub unsafe fn tagged_box_is<T>(p: gpointer) -> bool where T: 'static {
+    let type_id = *(p as *const TypeId);
+    type_id == TypeId::of::<T>()
+}
+
+pub unsafe fn tagged_box_ref<'a, T>(p: gpointer) -> Option<&'a T>
+    where T: 'static
+{
+    if p.is_null() || !tagged_box_is::<T>(p) {
+        return None;
+    }
+    let tagged = &*(p as *const Tagged<T>);
+    Some(&tagged.value)
+}
+
+// The type must have been checked with tagged_box_is()
+pub unsafe fn tagged_box_take<T>(p: gpointer) -> T where T: 'static {
+    debug_assert!(tagged_box_is::<T>(p));
+    let tagged: Box<Tagged<T>> = box_from_pointer(p);
+    tagged.value
+}
+
+pub unsafe extern "C" fn tagged_box_free<T>(p: gpointer) where T: 'static {
+    box_free::<Tagged<T>>(p);
+}
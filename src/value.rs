@@ -39,7 +39,6 @@ use std::mem;
 use std::ops::Deref;
 
 #[repr(C)]
-#[unsafe_no_drop_flag]
 pub struct Value(ffi::GValue);
 
 impl Drop for Value {
@@ -213,6 +212,25 @@ impl Value {
         }
     }
 
+    pub fn get_enum_lenient<T>(&self) -> enumeration::Lenient<T>
+        where T: EnumType
+    {
+        self.assert_enum_type::<T>();
+        unsafe {
+            let v = ffi::g_value_get_enum(self.as_raw());
+            enumeration::Lenient::from_int(v)
+        }
+    }
+
+    pub fn set_enum_lenient<T>(&mut self, val: enumeration::Lenient<T>)
+        where T: EnumType
+    {
+        self.assert_enum_type::<T>();
+        unsafe {
+            ffi::g_value_set_enum(self.as_mut_raw(), val.to_int());
+        }
+    }
+
     fn assert_flags_type<T>(&self) where T: FlagsType {
         debug_assert!(self.value_type() == flags::type_of::<T>(),
                       "GValue does not have the flags type {}",
@@ -236,6 +254,25 @@ impl Value {
         }
     }
 
+    pub fn get_flags_lenient<T>(&self) -> flags::Lenient<T>
+        where T: FlagsType
+    {
+        self.assert_flags_type::<T>();
+        unsafe {
+            let v = ffi::g_value_get_flags(self.as_raw());
+            flags::Lenient::from_uint(v)
+        }
+    }
+
+    pub fn set_flags_lenient<T>(&mut self, val: flags::Lenient<T>)
+        where T: FlagsType
+    {
+        self.assert_flags_type::<T>();
+        unsafe {
+            ffi::g_value_set_flags(self.as_mut_raw(), val.to_uint());
+        }
+    }
+
     pub fn get_string(&self) -> Option<&CStr> {
         unsafe {
             let ptr = ffi::g_value_get_string(self.as_raw());
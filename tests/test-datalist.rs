@@ -0,0 +1,160 @@
+// This file is part of Grust, GObject introspection bindings for Rust
+//
+// Copyright (C) 2015  Mikhail Zabaluev <mikhail.zabaluev@gmail.com>
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; either
+// version 2.1 of the License, or (at your option) any later version.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+
+#[macro_use]
+extern crate grust;
+
+extern crate glib_2_0_sys as glib;
+
+use grust::datalist::DataList;
+use grust::quark::Quark;
+
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::ptr;
+use std::rc::Rc;
+
+fn key_a() -> Quark { g_static_quark!("grust-test-datalist-a") }
+fn key_b() -> Quark { g_static_quark!("grust-test-datalist-b") }
+
+#[test]
+fn test_insert_get() {
+    let mut list = DataList::new();
+    assert!(list.get::<String>(key_a()).is_none());
+    list.insert(key_a(), String::from("a"));
+    list.insert(key_b(), 42u32);
+    assert_eq!(list.get::<String>(key_a()).unwrap(), "a");
+    assert_eq!(*list.get::<u32>(key_b()).unwrap(), 42);
+    assert!(list.get::<u32>(key_a()).is_none());
+    assert!(list.contains_key(key_a()));
+}
+
+#[test]
+fn test_insert_replaces() {
+    let mut list = DataList::new();
+    let rc = Rc::new(());
+    list.insert(key_a(), rc.clone());
+    list.insert(key_a(), String::from("a"));
+    assert_eq!(Rc::strong_count(&rc), 1);
+    assert_eq!(list.get::<String>(key_a()).unwrap(), "a");
+}
+
+#[test]
+fn test_remove() {
+    let mut list = DataList::new();
+    let rc = Rc::new(());
+    list.insert(key_a(), rc.clone());
+    list.remove(key_a());
+    assert_eq!(Rc::strong_count(&rc), 1);
+    assert!(!list.contains_key(key_a()));
+}
+
+#[test]
+fn test_take() {
+    let mut list = DataList::new();
+    let rc = Rc::new(());
+    list.insert(key_a(), rc.clone());
+    assert!(list.take::<String>(key_a()).is_none());
+    assert!(list.contains_key(key_a()));
+    let taken = list.take::<Rc<()>>(key_a()).unwrap();
+    assert!(!list.contains_key(key_a()));
+    assert_eq!(Rc::strong_count(&rc), 2);
+    drop(taken);
+    assert_eq!(Rc::strong_count(&rc), 1);
+}
+
+#[test]
+fn test_foreach() {
+    let mut list = DataList::new();
+    list.insert(key_a(), 1u32);
+    list.insert(key_b(), 2u32);
+    list.insert(Quark::from_static_str("grust-test-datalist-c\0"),
+                String::from("c"));
+    let mut sum = 0;
+    let mut keys = Vec::new();
+    list.foreach(|key, value: &u32| {
+        keys.push(key);
+        sum += *value;
+    });
+    assert_eq!(sum, 3);
+    keys.sort();
+    let mut expected = vec![key_a(), key_b()];
+    expected.sort();
+    assert_eq!(keys, expected);
+}
+
+#[test]
+fn test_foreach_panic() {
+    let mut list = DataList::new();
+    list.insert(key_a(), 1u32);
+    list.insert(key_b(), 2u32);
+    let mut count = 0;
+    let res = panic::catch_unwind(AssertUnwindSafe(|| {
+        list.foreach(|_, _: &u32| {
+            count += 1;
+            panic!("foreach panicked");
+        });
+    }));
+    assert!(res.is_err());
+    assert_eq!(count, 1);
+}
+
+#[test]
+fn test_keys() {
+    let mut list = DataList::new();
+    assert!(list.keys().is_empty());
+    list.insert(key_a(), 1u32);
+    list.insert(key_b(), String::from("b"));
+    let mut keys = list.keys();
+    keys.sort();
+    let mut expected = vec![key_a(), key_b()];
+    expected.sort();
+    assert_eq!(keys, expected);
+}
+
+#[test]
+fn test_drop() {
+    let rc = Rc::new(());
+    {
+        let mut list = DataList::new();
+        list.insert(key_a(), rc.clone());
+        list.insert(key_b(), rc.clone());
+        assert_eq!(Rc::strong_count(&rc), 3);
+    }
+    assert_eq!(Rc::strong_count(&rc), 1);
+}
+
+#[test]
+fn test_from_raw_mut() {
+    let rc = Rc::new(());
+    let mut raw: *mut glib::GData = ptr::null_mut();
+    unsafe {
+        glib::g_datalist_init(&mut raw);
+        {
+            let list = DataList::from_raw_mut(&mut raw);
+            list.insert(key_a(), rc.clone());
+        }
+        assert!(!raw.is_null());
+        {
+            let list = DataList::from_raw_mut(&mut raw);
+            assert!(list.get::<Rc<()>>(key_a()).is_some());
+            list.clear();
+        }
+    }
+    assert_eq!(Rc::strong_count(&rc), 1);
+}
@@ -0,0 +1,202 @@
+// This file is part of Grust, GObject introspection bindings for Rust
+//
+// Copyright (C) 2015  Mikhail Zabaluev <mikhail.zabaluev@gmail.com>
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; either
+// version 2.1 of the License, or (at your option) any later version.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+
+#[macro_use]
+extern crate grust;
+
+#[macro_use]
+extern crate grust_derive;
+
+use grust::enumeration;
+use grust::enumeration::{EnumValues, IntrospectedEnum, UnknownValue};
+use grust::error;
+use grust::error::{Error, DomainError};
+use grust::flags;
+use grust::flags::{FlagsValues, IntrospectedFlags};
+use grust::types::guint;
+use grust::value::Value;
+
+use std::error::Error as ErrorTrait;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, IntrospectedEnum)]
+enum MyEnum {
+    Foo = 1,
+    BarBaz = 2,
+    #[nick = "the-other"]
+    Other = 10
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, IntrospectedEnum, ErrorDomain)]
+#[domain = "grust-test-derived-error"]
+enum DerivedError {
+    Failed,
+    NotFound
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, IntrospectedFlags)]
+#[flags(READ = 0b1, WRITE = 0b10, EXECUTE = 0b100)]
+struct MyFlags(guint);
+
+g_type_register_enum!(MyEnum, "GrustTestDerivedEnum");
+g_type_register_flags!(MyFlags, "GrustTestDerivedFlags");
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, IntrospectedFlags)]
+#[flags(READ = 0b1, WRITE = 0b10, READ_WRITE = 0b11)]
+struct Access(guint);
+
+// Not registered with GLib
+#[derive(Copy, Clone, Debug, Eq, PartialEq, IntrospectedFlags)]
+#[flags(SHOWN = 0b1, ENABLED = 0b10)]
+struct Visibility(guint);
+
+#[test]
+fn test_enum_from_int() {
+    let v: MyEnum = enumeration::from_int(1).unwrap();
+    assert_eq!(v, MyEnum::Foo);
+    let v: MyEnum = enumeration::from_int(2).unwrap();
+    assert_eq!(v, MyEnum::BarBaz);
+    let v: MyEnum = enumeration::from_int(10).unwrap();
+    assert_eq!(v, MyEnum::Other);
+}
+
+#[test]
+fn test_enum_unknown_value() {
+    let res = enumeration::from_int::<MyEnum>(3);
+    assert_eq!(res.err().unwrap(), UnknownValue(3));
+}
+
+#[test]
+fn test_enum_to_int() {
+    assert_eq!(MyEnum::Foo.to_int(), 1);
+    assert_eq!(MyEnum::Other.to_int(), 10);
+    assert_eq!(DerivedError::Failed.to_int(), 0);
+    assert_eq!(DerivedError::NotFound.to_int(), 1);
+}
+
+#[test]
+fn test_enum_name() {
+    assert_eq!(MyEnum::Foo.name(), "foo");
+    assert_eq!(MyEnum::BarBaz.name(), "bar-baz");
+    assert_eq!(MyEnum::Other.name(), "the-other");
+}
+
+#[test]
+fn test_flags_consts() {
+    assert_eq!(MyFlags::READ.to_uint(), 0b1);
+    assert_eq!(MyFlags::WRITE.to_uint(), 0b10);
+    assert_eq!(MyFlags::EXECUTE.to_uint(), 0b100);
+}
+
+#[test]
+fn test_flags_from_uint() {
+    let v: MyFlags = flags::from_uint(0b101).unwrap();
+    assert_eq!(v, MyFlags(0b101));
+    let v: MyFlags = flags::from_uint(0).unwrap();
+    assert_eq!(v.to_uint(), 0);
+}
+
+#[test]
+fn test_flags_unknown() {
+    let err = flags::from_uint::<MyFlags>(0b1010).err().unwrap();
+    assert_eq!(err.actual(), 0b1010);
+    assert_eq!(err.known(), 0b10);
+    assert_eq!(err.unknown(), 0b1000);
+}
+
+#[test]
+fn test_error_domain() {
+    let domain = error::domain::<DerivedError>();
+    assert_eq!(domain.to_bytes(), b"grust-test-derived-error");
+    let err = Error::new(DerivedError::NotFound, "not found");
+    assert!(err.matches(DerivedError::NotFound));
+    let err = err.into_domain::<DerivedError>().unwrap();
+    assert_eq!(err.code(), error::Code::Known(DerivedError::NotFound));
+    let err = DomainError::new(DerivedError::Failed, "failed");
+    assert_eq!(err.description(), "failed");
+}
+
+#[test]
+fn test_enum_values() {
+    let values = MyEnum::values();
+    assert_eq!(values.len(), 3);
+    assert_eq!(values[1].value, 2);
+    assert_eq!(values[1].name, "MY_ENUM_BAR_BAZ");
+    assert_eq!(values[1].nick, "bar-baz");
+    assert_eq!(values[2].value, 10);
+    assert_eq!(values[2].nick, "the-other");
+}
+
+#[test]
+fn test_flags_values() {
+    let values = MyFlags::values();
+    assert_eq!(values.len(), 3);
+    assert_eq!(values[0].value, 0b1);
+    assert_eq!(values[0].name, "MY_FLAGS_READ");
+    assert_eq!(values[0].nick, "read");
+    assert_eq!(values[2].value, 0b100);
+    assert_eq!(values[2].nick, "execute");
+}
+
+#[test]
+fn test_registered_enum_type() {
+    let gtype = enumeration::type_of::<MyEnum>();
+    assert_eq!(gtype.name(), "GrustTestDerivedEnum");
+    assert_eq!(enumeration::type_of::<MyEnum>(), gtype);
+    let mut value = Value::new(gtype);
+    value.set_enum(MyEnum::Other);
+    assert_eq!(value.get_enum::<MyEnum>().unwrap(), MyEnum::Other);
+}
+
+#[test]
+fn test_registered_flags_type() {
+    let gtype = flags::type_of::<MyFlags>();
+    assert_eq!(gtype.name(), "GrustTestDerivedFlags");
+    let mut value = Value::new(gtype);
+    value.set_flags(MyFlags(0b11));
+    assert_eq!(value.get_flags::<MyFlags>().unwrap(), MyFlags(0b11));
+}
+
+#[test]
+fn test_flags_ops() {
+    let v = MyFlags::READ | MyFlags::WRITE;
+    assert_eq!(v, MyFlags(0b11));
+    assert!(v.contains(&MyFlags::WRITE));
+    assert!(!v.contains(&MyFlags::EXECUTE));
+    assert_eq!(v - MyFlags::READ, MyFlags::WRITE);
+    assert_eq!(!v, MyFlags::EXECUTE);
+    assert_eq!(MyFlags::all(), MyFlags(0b111));
+    let set: Vec<MyFlags> = v.iter().collect();
+    assert_eq!(set, [MyFlags::READ, MyFlags::WRITE]);
+}
+
+#[test]
+fn test_unregistered_flags_strings() {
+    let v = Visibility::SHOWN | Visibility::ENABLED;
+    assert_eq!(flags::to_string(&v), "shown | enabled");
+    assert_eq!(flags::to_string(&Visibility(0)), "0");
+    assert_eq!(flags::parse::<Visibility>("VISIBILITY_SHOWN | enabled").unwrap(),
+               v);
+}
+
+#[test]
+fn test_flags_iter_composite() {
+    let v = Access::READ_WRITE;
+    let set: Vec<Access> = v.iter().collect();
+    assert_eq!(set, vec![Access::READ, Access::WRITE]);
+    assert_eq!(flags::to_string(&v), "read | write");
+}
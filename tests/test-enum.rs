@@ -18,10 +18,14 @@
 
 #![allow(trivial_numeric_casts)]
 
+#[macro_use]
 extern crate grust;
 
 use grust::enumeration;
-use grust::enumeration::{IntrospectedEnum, UnknownValue};
+use grust::enumeration::{EnumClass, EnumValues, IntrospectedEnum, UnknownValue};
+use grust::enumeration::{Lenient, ValueDef};
+use grust::gtype;
+use grust::value::Value;
 
 use grust::types::gint;
 
@@ -53,6 +57,18 @@ impl IntrospectedEnum for MyEnum {
     }
 }
 
+impl EnumValues for MyEnum {
+    fn values() -> &'static [ValueDef] {
+        const VALUES: &'static [ValueDef] = &[
+            ValueDef { value: 1, name: "MY_ENUM_FOO", nick: "foo" },
+            ValueDef { value: 2, name: "MY_ENUM_BAR", nick: "bar" }
+        ];
+        VALUES
+    }
+}
+
+g_type_register_enum!(MyEnum, "GrustTestMyEnum");
+
 #[test]
 fn test_enum_from_int() {
     let v: MyEnum = enumeration::from_int(1).unwrap();
@@ -66,3 +82,66 @@ fn test_unknown_value() {
     let res = enumeration::from_int::<MyEnum>(0);
     assert_eq!(res.err().unwrap(), UnknownValue(0))
 }
+
+#[test]
+fn test_enum_class() {
+    let class = EnumClass::of::<MyEnum>();
+    assert_eq!(class.type_of(), enumeration::type_of::<MyEnum>());
+    assert_eq!(class.minimum(), 1);
+    assert_eq!(class.maximum(), 2);
+    let values = class.values();
+    assert_eq!(values.len(), 2);
+    assert_eq!(values[0].value(), 1);
+    assert_eq!(values[0].name(), "MY_ENUM_FOO");
+    assert_eq!(values[0].nick(), "foo");
+    assert_eq!(values[1].nick(), "bar");
+}
+
+#[test]
+fn test_enum_class_from_type() {
+    let gtype = enumeration::type_of::<MyEnum>();
+    let class = EnumClass::from_type(gtype).unwrap();
+    assert_eq!(class.clone().type_of(), gtype);
+    assert!(EnumClass::from_type(gtype::INT).is_none());
+    assert!(EnumClass::from_type(gtype::ENUM).is_none());
+    assert!(EnumClass::from_type(gtype::FLAGS).is_none());
+}
+
+#[test]
+fn test_enum_class_lookup() {
+    let class = EnumClass::of::<MyEnum>();
+    assert_eq!(class.value(2).unwrap().nick(), "bar");
+    assert!(class.value(3).is_none());
+    assert_eq!(class.value_by_name("MY_ENUM_BAR").unwrap().value(), 2);
+    assert!(class.value_by_name("bar").is_none());
+    assert_eq!(class.value_by_nick("foo").unwrap().value(), 1);
+    assert!(class.value_by_nick("MY_ENUM_FOO").is_none());
+    assert!(class.value_by_nick("fo\0o").is_none());
+}
+
+#[test]
+fn test_lenient() {
+    let v: Lenient<MyEnum> = Lenient::from_int(2);
+    assert_eq!(v, Lenient::Known(MyEnum::Bar));
+    assert!(v.is_known());
+    assert_eq!(v.to_int(), 2);
+    assert_eq!(v.known(), Some(MyEnum::Bar));
+    let v: Lenient<MyEnum> = Lenient::from_int(42);
+    assert_eq!(v, Lenient::Unknown(42));
+    assert!(!v.is_known());
+    assert_eq!(v.to_int(), 42);
+    assert_eq!(v.known(), None);
+    assert_eq!(Lenient::from(MyEnum::Foo), Lenient::Known(MyEnum::Foo));
+}
+
+#[test]
+fn test_value_lenient() {
+    let mut value = Value::new(enumeration::type_of::<MyEnum>());
+    value.set_enum_lenient(Lenient::<MyEnum>::Unknown(42));
+    assert!(value.get_enum::<MyEnum>().is_err());
+    let v = value.get_enum_lenient::<MyEnum>();
+    assert_eq!(v, Lenient::Unknown(42));
+    value.set_enum(MyEnum::Foo);
+    let v = value.get_enum_lenient::<MyEnum>();
+    assert_eq!(v, Lenient::Known(MyEnum::Foo));
+}
@@ -0,0 +1,190 @@
+// This file is part of Grust, GObject introspection bindings for Rust
+//
+// Copyright (C) 2015  Mikhail Zabaluev <mikhail.zabaluev@gmail.com>
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; either
+// version 2.1 of the License, or (at your option) any later version.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+
+// Extended error domains need GLib 2.68
+#![cfg(feature = "v2_68")]
+
+#![allow(trivial_numeric_casts)]
+
+#[macro_use]
+extern crate grust;
+
+extern crate glib_2_0_sys as glib;
+
+use grust::boxed;
+use grust::enumeration::IntrospectedEnum;
+use grust::enumeration::UnknownValue as UnknownEnumValue;
+use grust::error;
+use grust::error::{DomainError, Error};
+use grust::types::gint;
+use grust::value::Value;
+
+use std::error::Error as ErrorTrait;
+use std::fmt;
+use std::io;
+
+const C_QUX: gint = 1;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum CError {
+    Qux = C_QUX as isize
+}
+
+impl IntrospectedEnum for CError {
+
+    fn from_int(v: gint) -> Result<Self, UnknownEnumValue> {
+        match v {
+            C_QUX => Ok(CError::Qux),
+            _ => Err(UnknownEnumValue(v))
+        }
+    }
+
+    fn to_int(&self) -> gint {
+        *self as gint
+    }
+
+    fn name(&self) -> &'static str {
+        match *self {
+            CError::Qux => "qux"
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct CErrorDetail {
+    path: String,
+    line: u32
+}
+
+g_extended_error_domain!(CError, CErrorDetail, "grust-test-c-error");
+
+fn c_detail() -> CErrorDetail {
+    CErrorDetail { path: "test.txt".to_string(), line: 42 }
+}
+
+#[test]
+fn test_extended_error_new() {
+    let err = Error::new_extended(CError::Qux, "test error", c_detail());
+    assert!(err.matches(CError::Qux));
+    assert_eq!(err.domain().to_bytes(), b"grust-test-c-error");
+    assert_eq!(err.description(), "test error");
+    assert_eq!(err.extended_data::<CError>(), Some(&c_detail()));
+}
+
+#[test]
+fn test_extended_error_no_data() {
+    let mut err = Error::new(CError::Qux, "test error");
+    assert!(err.extended_data::<CError>().is_none());
+    err.set_extended_data::<CError>(c_detail());
+    assert_eq!(err.extended_data::<CError>(), Some(&c_detail()));
+}
+
+#[test]
+fn test_extended_data_other_domain() {
+    let err = Error::new(error::FileError::Noent, "test error");
+    assert!(err.extended_data::<CError>().is_none());
+}
+
+#[test]
+#[should_panic(expected = "not in the extended domain")]
+fn test_set_extended_data_other_domain() {
+    let mut err = Error::new(error::FileError::Noent, "test error");
+    err.set_extended_data::<CError>(c_detail());
+}
+
+#[test]
+fn test_extended_error_clone() {
+    let err = Error::new_extended(CError::Qux, "test error", c_detail());
+    let copy = err.clone();
+    drop(err);
+    assert_eq!(copy.extended_data::<CError>(), Some(&c_detail()));
+}
+
+#[test]
+fn test_extended_error_raw_copy() {
+    let err = Error::new_extended(CError::Qux, "test error", c_detail());
+    let copy = unsafe { Error::from_raw(glib::g_error_copy(err.as_ptr())) };
+    assert_eq!(copy.extended_data::<CError>(), Some(&c_detail()));
+}
+
+#[test]
+fn test_boxed_extended_error() {
+    let mut value = Value::new(boxed::type_of::<Error>());
+    value.take_boxed(Error::new_extended(CError::Qux, "test error",
+                                         c_detail()));
+    let value = value.clone();
+    let err = value.dup_boxed().unwrap();
+    assert_eq!(err.extended_data::<CError>(), Some(&c_detail()));
+}
+
+#[test]
+fn test_domain_error_extended_data() {
+    let mut err = DomainError::new_extended(CError::Qux, "test error",
+                                            c_detail());
+    assert_eq!(err.extended_data(), Some(&c_detail()));
+    let detail = CErrorDetail { path: "other.txt".to_string(), line: 1 };
+    err.set_extended_data(detail.clone());
+    let err = Error::from(err);
+    assert_eq!(err.extended_data::<CError>(), Some(&detail));
+}
+
+#[derive(Debug)]
+struct TestStdError;
+
+impl fmt::Display for TestStdError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "std error")
+    }
+}
+
+impl ErrorTrait for TestStdError {
+    fn description(&self) -> &str { "std error" }
+}
+
+#[test]
+fn test_error_from_std() {
+    let mut err = Error::from_std(TestStdError);
+    assert!(err.matches(error::RustError::Failed));
+    assert_eq!(err.description(), "std error");
+    err.prefix("outer: ");
+    assert_eq!(format!("{}", err), "outer: std error");
+    let source = err.source().unwrap();
+    assert_eq!(format!("{}", source), "std error");
+    assert!(source.downcast_ref::<TestStdError>().is_some());
+}
+
+#[test]
+fn test_error_from_std_clone() {
+    let err = Error::from_std(TestStdError).clone();
+    assert!(err.wrapped().unwrap().downcast_ref::<TestStdError>().is_some());
+    let err = err.into_domain::<error::RustError>().unwrap();
+    assert!(err.source().is_some());
+}
+
+#[test]
+fn test_error_not_wrapped() {
+    let err = Error::new(error::FileError::Noent, "test error");
+    assert!(err.wrapped().is_none());
+}
+
+#[test]
+fn test_wrapped_io_error() {
+    let wrapped = io::Error::new(io::ErrorKind::TimedOut, "timed out");
+    let err = Error::from_std(wrapped);
+    assert_eq!(err.to_io_error().kind(), io::ErrorKind::TimedOut);
+}
@@ -30,11 +30,12 @@ use grust::error::{Error, Domain, DomainError};
 
 use grust::boxed;
 use grust::quark::Quark;
-use grust::types::gint;
+use grust::types::{gint, FALSE, TRUE};
 use grust::value::Value;
 
 use std::error::Error as ErrorTrait;
 use std::ffi::CString;
+use std::ptr;
 use std::str;
 
 const NON_UTF8: &'static [u8] = b"U can't parse this.\x9c Hammer time!";
@@ -77,7 +78,7 @@ impl IntrospectedEnum for AError {
 
 impl Domain for AError {
     fn domain() -> Quark {
-        g_static_quark!(b"a-error\0")
+        g_static_quark!("a-error")
     }
 }
 
@@ -108,7 +109,7 @@ impl IntrospectedEnum for BError {
 
 impl Domain for BError {
     fn domain() -> Quark {
-        g_static_quark!(b"b-error\0")
+        g_static_quark!("b-error")
     }
 }
 
@@ -359,6 +360,88 @@ fn test_error_match_macro() {
     }
 }
 
+#[test]
+fn test_error_match_code() {
+    let err = new_error::<AError>(A_BAR, b"test error");
+    let res = g_error_match! {
+        (err) {
+            (_e: DomainError<AError>) if code AError::Foo => 1,
+            (_e: DomainError<BError>) => 2,
+            (e: DomainError<AError>) if code AError::Foo | AError::Bar => {
+                assert_eq!(e.code(), error::Code::Known(AError::Bar));
+                3
+            },
+            other _e => 4
+        }
+    };
+    assert_eq!(res, 3);
+}
+
+#[test]
+fn test_error_match_unknown_code() {
+    let err = new_error::<AError>(42, b"test error");
+    let res = g_error_match! {
+        (err) {
+            (_e: DomainError<AError>) if code AError::Foo | AError::Bar => 1,
+            (e: DomainError<AError>) if unknown code => {
+                assert_eq!(e.code(), error::Code::Unknown(42));
+                2
+            },
+            other _e => 3
+        }
+    };
+    assert_eq!(res, 2);
+}
+
+#[test]
+fn test_error_match_guard() {
+    let err = new_error::<AError>(A_FOO, b"test error");
+    let res = g_error_match! {
+        (err) {
+            (e: DomainError<AError>) if e.description() == "other" => 1,
+            (e: DomainError<AError>) if e.description() == "test error" => 2,
+            other _e => 3,
+        }
+    };
+    assert_eq!(res, 2);
+}
+
+#[test]
+fn test_error_match_fallthrough() {
+    let err = new_error::<BError>(B_BAZ, b"test error");
+    let res = g_error_match! {
+        (err) {
+            (_e: DomainError<AError>) => 1,
+            (_e: DomainError<BError>) if unknown code => 2,
+            other e => {
+                assert!(e.matches(BError::Baz));
+                3
+            }
+        }
+    };
+    assert_eq!(res, 3);
+}
+
+#[test]
+fn test_error_match_result() {
+    let err = new_error::<AError>(A_FOO, b"test error");
+    let res = g_error_match! {
+        (err) {
+            (_e: DomainError<AError>) if code AError::Foo => 1,
+            (_e: DomainError<BError>) => 2,
+        }
+    };
+    assert_eq!(res.unwrap(), 1);
+    let err = new_error::<AError>(A_BAR, b"test error");
+    let res = g_error_match! {
+        (err) {
+            (_e: DomainError<AError>) if code AError::Foo => 1,
+            (_e: DomainError<BError>) => 2
+        }
+    };
+    assert!(res.unwrap_err().matches(AError::Bar));
+}
+
 #[test]
 fn test_boxed_error() {
     let mut value = Value::new(boxed::type_of::<Error>());
@@ -374,3 +457,175 @@ fn test_boxed_error() {
         }
     }
 }
+
+#[test]
+fn test_error_new() {
+    let err = Error::new(AError::Bar, "test error");
+    assert_eq!(err.key(), (error::domain::<AError>(), A_BAR));
+    assert!(err.matches(AError::Bar));
+    assert_eq!(err.description(), "test error");
+}
+
+#[test]
+fn test_error_new_truncated() {
+    let err = Error::new(AError::Foo, "test\0error");
+    assert_eq!(err.description(), "test");
+}
+
+#[test]
+fn test_error_new_fmt() {
+    let err = Error::new_fmt(BError::Baz, format_args!("{} error {}", "test", 42));
+    assert!(err.matches(BError::Baz));
+    assert_eq!(err.description(), "test error 42");
+    let err = g_error_new!(AError::Foo, "{}-{}", 1, 2);
+    assert!(err.matches(AError::Foo));
+    assert_eq!(err.description(), "1-2");
+}
+
+#[test]
+fn test_domain_error_new() {
+    let err = DomainError::new(AError::Foo, "test error");
+    assert_eq!(err.code(), error::Code::Known(AError::Foo));
+    assert_eq!(err.description(), "test error");
+    let err = DomainError::new_fmt(AError::Bar, format_args!("error {}", 2));
+    assert_eq!(err.code(), error::Code::Known(AError::Bar));
+    assert_eq!(err.description(), "error 2");
+}
+
+#[test]
+fn test_error_into_raw() {
+    let err = Error::new(AError::Foo, "test error");
+    let ptr = err.as_ptr();
+    let raw = err.into_raw();
+    assert!(raw as *const _ == ptr);
+    unsafe {
+        assert_eq!((*raw).code, A_FOO);
+        let err = Error::from_raw(raw);
+        assert_eq!(err.description(), "test error");
+    }
+    let raw = DomainError::new(BError::Baz, "test error").into_raw();
+    let err = unsafe { Error::from_raw(raw) };
+    assert!(err.matches(BError::Baz));
+}
+
+#[test]
+fn test_call() {
+    let res = error::call(|_| 42);
+    assert_eq!(res.unwrap(), 42);
+    let res: Result<(), Error> = error::call(|err| unsafe {
+        *err = Error::new(AError::Foo, "test error").into_raw();
+    });
+    assert!(res.unwrap_err().matches(AError::Foo));
+}
+
+#[test]
+fn test_call_bool() {
+    let res = error::call_bool(|_| TRUE);
+    assert!(res.is_ok());
+    let path = CString::new("/nonexistent/grust/test/file").unwrap();
+    let res = error::call_bool(|err| unsafe {
+        let mut contents = ptr::null_mut();
+        glib::g_file_get_contents(path.as_ptr(), &mut contents,
+                                  ptr::null_mut(), err)
+    });
+    let err = res.unwrap_err();
+    assert_eq!(err.domain().to_bytes(), b"g-file-error-quark");
+}
+
+#[test]
+fn test_call_ptr() {
+    let mut val = 42;
+    let res = error::call_ptr(|_| &mut val as *mut i32);
+    assert_eq!(unsafe { *res.unwrap() }, 42);
+    let path = CString::new("/nonexistent/grust/test/dir").unwrap();
+    let res = error::call_ptr(|err| unsafe {
+        glib::g_dir_open(path.as_ptr(), 0, err)
+    });
+    let err = res.unwrap_err();
+    assert_eq!(err.domain().to_bytes(), b"g-file-error-quark");
+}
+
+#[test]
+#[cfg_attr(debug_assertions, should_panic(expected = "without setting the error"))]
+fn test_call_bool_unreported() {
+    let res = error::call_bool(|_| FALSE);
+    assert!(res.is_err());
+}
+
+#[test]
+#[cfg_attr(debug_assertions, should_panic(expected = "without signalling failure"))]
+fn test_call_ptr_unexpected_error() {
+    let mut val = 42;
+    let res = error::call_ptr(|err| unsafe {
+        *err = Error::new(AError::Foo, "test error").into_raw();
+        &mut val as *mut i32
+    });
+    assert!(res.is_ok());
+}
+
+#[test]
+fn test_error_prefix() {
+    let mut err = Error::new(AError::Foo, "test error");
+    err.prefix("failed to frobnicate: ");
+    assert!(err.matches(AError::Foo));
+    assert_eq!(err.description(), "failed to frobnicate: test error");
+    let mut err = DomainError::new(BError::Baz, "test error");
+    err.prefix("context\0ignored: ");
+    assert_eq!(err.description(), "contexttest error");
+}
+
+#[test]
+fn test_error_no_source() {
+    let err = Error::new(AError::Foo, "test error");
+    assert!(err.source().is_none());
+}
+
+#[test]
+fn test_file_error_domain() {
+    let path = CString::new("/nonexistent/grust/test/file").unwrap();
+    let res = error::call_bool(|err| unsafe {
+        let mut contents = ptr::null_mut();
+        glib::g_file_get_contents(path.as_ptr(), &mut contents,
+                                  ptr::null_mut(), err)
+    });
+    let err = res.unwrap_err();
+    assert!(err.matches(error::FileError::Noent));
+    let err = err.into_domain::<error::FileError>().unwrap();
+    assert_eq!(err.code(), error::Code::Known(error::FileError::Noent));
+}
+
+#[test]
+fn test_file_error_from_errno() {
+    use error::FileError;
+    assert_eq!(FileError::from_errno(2), FileError::Noent);
+    assert_eq!(FileError::from_errno(13), FileError::Acces);
+    assert_eq!(FileError::from_errno(-1), FileError::Failed);
+}
+
+#[test]
+fn test_error_from_io_error() {
+    use std::io;
+    let io_err = io::Error::from_raw_os_error(17);
+    let err = Error::from(io_err);
+    assert!(err.matches(error::FileError::Exist));
+    let io_err = io::Error::new(io::ErrorKind::NotFound, "no such thing");
+    let err = Error::from(io_err);
+    assert!(err.matches(error::FileError::Noent));
+    assert_eq!(err.description(), "no such thing");
+    let io_err = io::Error::new(io::ErrorKind::Other, "whatever");
+    let err = Error::from(io_err);
+    assert!(err.matches(error::FileError::Failed));
+}
+
+#[test]
+fn test_error_to_io_error() {
+    use std::io;
+    let err = Error::new(error::FileError::Acces, "access denied");
+    let io_err = err.to_io_error();
+    assert_eq!(io_err.kind(), io::ErrorKind::PermissionDenied);
+    assert_eq!(io_err.to_string(), "access denied");
+    let err = Error::new(AError::Foo, "test error");
+    let io_err = err.to_io_error();
+    assert_eq!(io_err.kind(), io::ErrorKind::Other);
+    assert_eq!(io_err.to_string(), "test error");
+}
@@ -0,0 +1,237 @@
+// This file is part of Grust, GObject introspection bindings for Rust
+//
+// Copyright (C) 2015  Mikhail Zabaluev <mikhail.zabaluev@gmail.com>
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; either
+// version 2.1 of the License, or (at your option) any later version.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+
+#[macro_use]
+extern crate grust;
+
+use grust::flags;
+use grust::flags::{FlagsClass, FlagsValues, IntrospectedFlags, UnknownFlags};
+use grust::flags::{Lenient, ValueDef};
+use grust::gtype;
+use grust::types::guint;
+use grust::value::Value;
+
+const FOO: guint = 0b001;
+const BAR: guint = 0b010;
+const BAZ: guint = 0b100;
+const KNOWN_MASK: guint = FOO | BAR | BAZ;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+struct MyFlags(guint);
+
+impl IntrospectedFlags for MyFlags {
+
+    fn from_uint(v: guint) -> Result<MyFlags, UnknownFlags> {
+        if v & !KNOWN_MASK == 0 {
+            Ok(MyFlags(v))
+        } else {
+            Err(UnknownFlags::new(v, KNOWN_MASK))
+        }
+    }
+
+    fn to_uint(&self) -> guint {
+        self.0
+    }
+}
+
+impl FlagsValues for MyFlags {
+    fn values() -> &'static [ValueDef] {
+        const VALUES: &'static [ValueDef] = &[
+            ValueDef { value: FOO, name: "MY_FLAGS_FOO", nick: "foo" },
+            ValueDef { value: BAR, name: "MY_FLAGS_BAR", nick: "bar" },
+            ValueDef { value: BAZ, name: "MY_FLAGS_BAZ", nick: "baz" }
+        ];
+        VALUES
+    }
+}
+
+g_type_register_flags!(MyFlags, "GrustTestMyFlags");
+g_impl_flags_ops!(MyFlags);
+
+#[test]
+fn test_flags_class() {
+    let class = FlagsClass::of::<MyFlags>();
+    assert_eq!(class.type_of(), flags::type_of::<MyFlags>());
+    assert_eq!(class.mask(), KNOWN_MASK);
+    let values = class.values();
+    assert_eq!(values.len(), 3);
+    assert_eq!(values[1].value(), BAR);
+    assert_eq!(values[1].name(), "MY_FLAGS_BAR");
+    assert_eq!(values[1].nick(), "bar");
+}
+
+#[test]
+fn test_flags_class_from_type() {
+    let gtype = flags::type_of::<MyFlags>();
+    let class = FlagsClass::from_type(gtype).unwrap();
+    assert_eq!(class.clone().type_of(), gtype);
+    assert!(FlagsClass::from_type(gtype::ENUM).is_none());
+    assert!(FlagsClass::from_type(gtype::FLAGS).is_none());
+    assert!(FlagsClass::from_type(gtype::UINT).is_none());
+}
+
+#[test]
+fn test_flags_class_lookup() {
+    let class = FlagsClass::of::<MyFlags>();
+    assert_eq!(class.first_value(BAR | BAZ).unwrap().nick(), "bar");
+    assert!(class.first_value(0b1000).is_none());
+    assert_eq!(class.value_by_name("MY_FLAGS_BAZ").unwrap().value(), BAZ);
+    assert_eq!(class.value_by_nick("foo").unwrap().value(), FOO);
+    assert!(class.value_by_nick("qux").is_none());
+}
+
+#[test]
+fn test_flags_class_decompose() {
+    let class = FlagsClass::of::<MyFlags>();
+    let (values, rest) = class.decompose(FOO | BAZ | 0b10000);
+    let nicks: Vec<&str> = values.iter().map(|v| v.nick()).collect();
+    assert_eq!(nicks, ["foo", "baz"]);
+    assert_eq!(rest, 0b10000);
+    let (values, rest) = class.decompose(0);
+    assert!(values.is_empty());
+    assert_eq!(rest, 0);
+}
+
+#[test]
+fn test_to_string() {
+    assert_eq!(flags::to_string(&MyFlags(FOO)), "foo");
+    assert_eq!(flags::to_string(&MyFlags(FOO | BAZ)), "foo | baz");
+    assert_eq!(flags::to_string(&MyFlags(0)), "0");
+}
+
+#[test]
+fn test_to_string_unknown_bits() {
+    assert_eq!(flags::to_string(&MyFlags(BAR | 0b11000)), "bar | 0x18");
+}
+
+#[test]
+fn test_parse() {
+    let v: MyFlags = flags::parse("foo | baz").unwrap();
+    assert_eq!(v, MyFlags(FOO | BAZ));
+    let v: MyFlags = flags::parse("MY_FLAGS_BAR|foo").unwrap();
+    assert_eq!(v, MyFlags(FOO | BAR));
+    let v: MyFlags = flags::parse("bar | 4").unwrap();
+    assert_eq!(v, MyFlags(BAR | BAZ));
+    let v: MyFlags = flags::parse("0x3").unwrap();
+    assert_eq!(v, MyFlags(FOO | BAR));
+    let v: MyFlags = flags::parse("").unwrap();
+    assert_eq!(v, MyFlags(0));
+}
+
+#[test]
+fn test_parse_round_trip() {
+    let v = MyFlags(FOO | BAR | BAZ);
+    let s = flags::to_string(&v);
+    assert_eq!(flags::parse::<MyFlags>(&s).unwrap(), v);
+}
+
+#[test]
+fn test_parse_unknown_names() {
+    match flags::parse::<MyFlags>("foo | qux | quux") {
+        Err(flags::ParseFlagsError::UnknownNames(names)) => {
+            assert_eq!(names, ["qux", "quux"]);
+        }
+        other => panic!("unexpected result {:?}", other)
+    }
+}
+
+#[test]
+fn test_parse_unknown_flags() {
+    match flags::parse::<MyFlags>("foo | 0x18") {
+        Err(flags::ParseFlagsError::UnknownFlags(err)) => {
+            assert_eq!(err.actual(), FOO | 0x18);
+            assert_eq!(err.unknown(), 0x18);
+        }
+        other => panic!("unexpected result {:?}", other)
+    }
+}
+
+#[test]
+fn test_flags_set_methods() {
+    let v = MyFlags(FOO | BAR);
+    assert!(v.contains(&MyFlags(FOO)));
+    assert!(v.contains(&MyFlags(FOO | BAR)));
+    assert!(!v.contains(&MyFlags(FOO | BAZ)));
+    assert!(v.intersects(&MyFlags(FOO | BAZ)));
+    assert!(!v.intersects(&MyFlags(BAZ)));
+    assert!(!v.is_empty());
+    assert!(MyFlags::empty().is_empty());
+    assert_eq!(MyFlags::all(), MyFlags(KNOWN_MASK));
+}
+
+#[test]
+fn test_flags_insert_remove_toggle() {
+    let mut v = MyFlags::empty();
+    v.insert(MyFlags(FOO | BAZ));
+    assert_eq!(v, MyFlags(FOO | BAZ));
+    v.remove(MyFlags(FOO));
+    assert_eq!(v, MyFlags(BAZ));
+    v.toggle(MyFlags(BAR | BAZ));
+    assert_eq!(v, MyFlags(BAR));
+}
+
+#[test]
+fn test_flags_iter() {
+    let v = MyFlags(FOO | BAZ);
+    let set: Vec<MyFlags> = v.iter().collect();
+    assert_eq!(set, [MyFlags(FOO), MyFlags(BAZ)]);
+    assert_eq!(MyFlags::empty().iter().count(), 0);
+}
+
+#[test]
+fn test_flags_ops() {
+    assert_eq!(MyFlags(FOO) | MyFlags(BAR), MyFlags(FOO | BAR));
+    assert_eq!(MyFlags(FOO | BAR) & MyFlags(BAR | BAZ), MyFlags(BAR));
+    assert_eq!(MyFlags(FOO | BAR) ^ MyFlags(BAR | BAZ), MyFlags(FOO | BAZ));
+    assert_eq!(MyFlags(FOO | BAR) - MyFlags(BAR), MyFlags(FOO));
+    assert_eq!(!MyFlags(FOO), MyFlags(BAR | BAZ));
+    let mut v = MyFlags(FOO);
+    v |= MyFlags(BAZ);
+    assert_eq!(v, MyFlags(FOO | BAZ));
+    v &= MyFlags(BAZ);
+    assert_eq!(v, MyFlags(BAZ));
+    v ^= MyFlags(FOO | BAZ);
+    assert_eq!(v, MyFlags(FOO));
+    v -= MyFlags(FOO);
+    assert!(v.is_empty());
+}
+
+#[test]
+fn test_lenient() {
+    let v: Lenient<MyFlags> = Lenient::from_uint(FOO | BAR);
+    assert_eq!(*v.known(), MyFlags(FOO | BAR));
+    assert_eq!(v.unknown(), 0);
+    assert_eq!(v.to_uint(), FOO | BAR);
+    let v: Lenient<MyFlags> = Lenient::from_uint(FOO | 0b11000);
+    assert_eq!(*v.known(), MyFlags(FOO));
+    assert_eq!(v.unknown(), 0b11000);
+    assert_eq!(v.to_uint(), FOO | 0b11000);
+    assert_eq!(v.into_known(), MyFlags(FOO));
+    assert_eq!(Lenient::from(MyFlags(BAZ)).to_uint(), BAZ);
+}
+
+#[test]
+fn test_value_lenient() {
+    let mut value = Value::new(flags::type_of::<MyFlags>());
+    value.set_flags_lenient(Lenient::<MyFlags>::from_uint(BAR | 0b10000));
+    assert!(value.get_flags::<MyFlags>().is_err());
+    let v = value.get_flags_lenient::<MyFlags>();
+    assert_eq!(*v.known(), MyFlags(BAR));
+    assert_eq!(v.unknown(), 0b10000);
+    assert_eq!(v.to_uint(), BAR | 0b10000);
+}
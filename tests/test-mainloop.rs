@@ -16,14 +16,20 @@
 // License along with this library; if not, write to the Free Software
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 
+#[macro_use]
 extern crate grust;
 
 use grust::mainloop;
-use grust::mainloop::{LoopRunner, Source, SourceCallback};
+use grust::mainloop::{LoopRunner, MainContext, MainLoop, Source, SourceCallback};
+use grust::mainloop::LocalSourceCallback;
 use grust::mainloop::CallbackResult::{Continue, Remove};
+use grust::wrap::Wrapper;
 
+use std::cell::Cell;
+use std::rc::Rc;
 use std::sync::mpsc;
 use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 #[test]
 fn test_invoke_once() {
@@ -165,3 +171,394 @@ fn test_attached_source() {
     });
     assert_eq!(rx.iter().count(), 1);
 }
+
+#[test]
+fn test_iteration() {
+    let (tx, rx) = mpsc::channel();
+    let ctx = MainContext::new();
+    let _acquired = ctx.acquire().unwrap();
+    assert!(!ctx.pending());
+    let source = mainloop::idle_source_new();
+    source.set_callback(SourceCallback::once(move || {
+        tx.send(()).unwrap();
+    }));
+    source.attach(&ctx);
+    assert!(ctx.pending());
+    assert!(ctx.iteration(false));
+    assert!(rx.try_recv().is_ok());
+    assert!(!ctx.pending());
+    assert!(!ctx.iteration(false));
+}
+
+#[test]
+fn test_acquire() {
+    let ctx = MainContext::new();
+    assert!(!ctx.is_owner());
+    {
+        let guard = ctx.acquire().unwrap();
+        assert!(guard.context().is_owner());
+        let ctx2 = ctx.clone();
+        let acquired_elsewhere = thread::spawn(move || {
+            ctx2.acquire().is_some()
+        }).join().unwrap();
+        assert!(!acquired_elsewhere);
+    }
+    assert!(!ctx.is_owner());
+    let ctx2 = ctx.clone();
+    let acquired_elsewhere = thread::spawn(move || {
+        ctx2.acquire().is_some()
+    }).join().unwrap();
+    assert!(acquired_elsewhere);
+}
+
+#[test]
+fn test_wakeup() {
+    let ctx = MainContext::new();
+    let _acquired = ctx.acquire().unwrap();
+    let ctx2 = ctx.clone();
+    thread::spawn(move || {
+        ctx2.wakeup();
+    });
+    // Returns once woken up, with no sources dispatched
+    assert!(!ctx.iteration(true));
+}
+
+#[test]
+fn test_thread_default() {
+    assert!(MainContext::thread_default().is_none());
+    let global = MainContext::ref_thread_default();
+    assert!(global.as_ptr() == MainContext::default().as_ptr());
+    let runner = LoopRunner::new();
+    runner.run_after(|ml| {
+        let ctx = MainContext::thread_default().unwrap();
+        assert!(ctx.as_ptr() == ml.get_context().as_ptr());
+        let ctx = MainContext::ref_thread_default();
+        assert!(ctx.as_ptr() == ml.get_context().as_ptr());
+        let mlc = ml.clone();
+        let source = mainloop::idle_source_new();
+        source.set_callback(SourceCallback::once(move || {
+            mlc.quit();
+        }));
+        source.attach(ml.get_context());
+    });
+    assert!(MainContext::thread_default().is_none());
+}
+
+#[test]
+fn test_push_thread_default() {
+    let ctx1 = MainContext::new();
+    let ctx2 = MainContext::new();
+    {
+        let guard1 = ctx1.push_thread_default();
+        assert!(guard1.context().as_ptr() == ctx1.as_ptr());
+        let current = MainContext::thread_default().unwrap();
+        assert!(current.as_ptr() == ctx1.as_ptr());
+        {
+            let _guard2 = ctx2.push_thread_default();
+            let current = MainContext::thread_default().unwrap();
+            assert!(current.as_ptr() == ctx2.as_ptr());
+        }
+        let current = MainContext::thread_default().unwrap();
+        assert!(current.as_ptr() == ctx1.as_ptr());
+    }
+    assert!(MainContext::thread_default().is_none());
+}
+
+#[test]
+#[should_panic(expected = "out of order")]
+fn test_push_thread_default_out_of_order() {
+    let ctx1 = MainContext::new();
+    let ctx2 = MainContext::new();
+    let guard1 = ctx1.push_thread_default();
+    let _guard2 = ctx2.push_thread_default();
+    drop(guard1);
+}
+
+#[test]
+fn test_main_loop_run() {
+    let ctx = MainContext::new();
+    let ml = MainLoop::new(Some(&ctx), false);
+    assert!(!ml.is_running());
+    assert!(ml.get_context().as_ptr() == ctx.as_ptr());
+    let mlc = ml.clone();
+    let source = mainloop::idle_source_new();
+    source.set_callback(SourceCallback::once(move || {
+        assert!(mlc.is_running());
+        mlc.quit();
+    }));
+    source.attach(&ctx);
+    ml.run();
+    assert!(!ml.is_running());
+}
+
+#[test]
+fn test_main_loop_default_context() {
+    let ml = MainLoop::new(None, false);
+    assert!(ml.get_context().as_ptr() == MainContext::default().as_ptr());
+}
+
+#[test]
+fn test_run_until() {
+    let runner = LoopRunner::new();
+    let res = runner.run_until(|quitter| {
+        thread::spawn(move || {
+            let ctx = quitter.get_context();
+            let qc = quitter.clone();
+            ctx.invoke(SourceCallback::once(move || {
+                qc.quit(6 * 7);
+            }));
+        });
+    });
+    assert_eq!(res, 42);
+}
+
+#[test]
+fn test_run_until_first_result() {
+    let runner = LoopRunner::new();
+    let res = runner.run_until(|quitter| {
+        let source = mainloop::idle_source_new();
+        source.set_callback(SourceCallback::once(move || {
+            quitter.quit("first");
+            quitter.quit("second");
+        }));
+        source.attach(quitter.get_context());
+    });
+    assert_eq!(res, "first");
+}
+
+#[test]
+fn test_local_callback() {
+    let count = Rc::new(Cell::new(0));
+    let runner = LoopRunner::new();
+    runner.run_after(|ml| {
+        let source = mainloop::idle_source_new().into_local();
+        let mlc = ml.clone();
+        let cc = count.clone();
+        source.set_callback(LocalSourceCallback::new(move || {
+            cc.set(cc.get() + 1);
+            if cc.get() < 2 {
+                Continue
+            } else {
+                mlc.quit();
+                Remove
+            }
+        }));
+        source.attach(ml.get_context());
+    });
+    assert_eq!(count.get(), 2);
+}
+
+#[test]
+fn test_local_callback_attached() {
+    let flag = Rc::new(Cell::new(false));
+    let ctx = MainContext::new();
+    let _acquired = ctx.acquire().unwrap();
+    let source = mainloop::idle_source_new().into_local();
+    let attached = source.attach(&ctx);
+    let fc = flag.clone();
+    attached.as_source().set_callback(LocalSourceCallback::once(move || {
+        fc.set(true);
+    }));
+    assert!(ctx.iteration(false));
+    assert!(flag.get());
+}
+
+#[test]
+fn test_local_callback_dropped_after_release() {
+    struct DropFlag(Rc<Cell<bool>>);
+    impl Drop for DropFlag {
+        fn drop(&mut self) { self.0.set(true); }
+    }
+
+    let dropped = Rc::new(Cell::new(false));
+    let ctx = MainContext::new();
+    {
+        let _acquired = ctx.acquire().unwrap();
+        let source = mainloop::idle_source_new().into_local();
+        let flag = DropFlag(dropped.clone());
+        source.set_callback(LocalSourceCallback::once(move || {
+            let _ = &flag;
+        }));
+        source.attach(&ctx);
+    }
+    assert!(!dropped.get());
+    // The closure is dropped on the thread that created it
+    drop(ctx);
+    assert!(dropped.get());
+}
+
+#[test]
+#[should_panic(expected = "owned by the current thread")]
+fn test_local_callback_not_owner() {
+    let ctx = MainContext::new();
+    let source = mainloop::idle_source_new().into_local();
+    source.set_callback(LocalSourceCallback::once(|| {}));
+    source.attach(&ctx);
+}
+
+#[test]
+fn test_source_name() {
+    let source = mainloop::idle_source_new();
+    assert!(source.name().is_none());
+    source.set_name(g_str!("test idle"));
+    let name = source.name().unwrap();
+    source.set_name(g_str!("renamed"));
+    assert_eq!(name.to_bytes(), b"test idle");
+    assert_eq!(source.name().unwrap().to_bytes(), b"renamed");
+}
+
+#[test]
+fn test_source_properties() {
+    let source = mainloop::idle_source_new();
+    assert_eq!(source.priority(), mainloop::PRIORITY_DEFAULT_IDLE);
+    source.set_priority(mainloop::PRIORITY_HIGH);
+    assert_eq!(source.priority(), mainloop::PRIORITY_HIGH);
+    assert!(!source.can_recurse());
+    source.set_can_recurse(true);
+    assert!(source.can_recurse());
+    assert!(source.ready_time().is_none());
+    let t = mainloop::monotonic_time();
+    source.set_ready_time(Some(t));
+    assert_eq!(source.ready_time(), Some(t));
+    source.set_ready_time(None);
+    assert!(source.ready_time().is_none());
+}
+
+#[test]
+fn test_source_id() {
+    let ctx = MainContext::new();
+    let _acquired = ctx.acquire().unwrap();
+    let source = mainloop::idle_source_new();
+    source.set_name(g_str!("findable"));
+    let attached = source.attach(&ctx);
+    let id = attached.id();
+    assert!(id != 0);
+    let found = ctx.find_source_by_id(id).unwrap();
+    assert!(found.as_ptr() == attached.as_ptr());
+    assert_eq!(found.as_source().name().unwrap().to_bytes(), b"findable");
+    assert!(!attached.is_destroyed());
+    attached.destroy();
+    assert!(attached.is_destroyed());
+    assert!(ctx.find_source_by_id(id).is_none());
+}
+
+#[test]
+#[should_panic(expected = "owned by the current thread")]
+fn test_find_source_not_owner() {
+    let ctx = MainContext::new();
+    let attached = mainloop::idle_source_new().attach(&ctx);
+    ctx.find_source_by_id(attached.id());
+}
+
+#[test]
+fn test_source_time() {
+    let ctx = MainContext::new();
+    let _acquired = ctx.acquire().unwrap();
+    let (tx, rx) = mpsc::channel();
+    let source = mainloop::timeout_source_new(1);
+    source.set_callback(SourceCallback::once(move || {
+        tx.send(()).unwrap();
+    }));
+    let attached = source.attach(&ctx);
+    let t = attached.get_time();
+    assert!(t <= mainloop::monotonic_time());
+    while rx.try_recv().is_err() {
+        ctx.iteration(true);
+    }
+    assert!(attached.get_time() >= t);
+}
+
+#[test]
+fn test_ready_time() {
+    let ctx = MainContext::new();
+    let _acquired = ctx.acquire().unwrap();
+    let (tx, rx) = mpsc::channel();
+    let parent = mainloop::timeout_source_new_seconds(3600);
+    parent.set_callback(SourceCallback::new(move || {
+        tx.send(()).unwrap();
+        Continue
+    }));
+    let attached = parent.attach(&ctx);
+    assert!(!ctx.iteration(false));
+    let now = attached.get_time();
+    attached.as_source().set_ready_time(Some(now));
+    assert!(ctx.iteration(false));
+    assert!(rx.try_recv().is_ok());
+}
+
+#[test]
+fn test_child_source() {
+    let ctx = MainContext::new();
+    let _acquired = ctx.acquire().unwrap();
+    let (tx, rx) = mpsc::channel();
+    let parent = mainloop::timeout_source_new_seconds(3600);
+    parent.set_callback(SourceCallback::new(move || {
+        tx.send(()).unwrap();
+        Continue
+    }));
+    let child = mainloop::idle_source_new();
+    parent.add_child_source(&child);
+    parent.attach(&ctx);
+    // The parent gets dispatched when its child source is ready
+    assert!(ctx.iteration(false));
+    assert!(rx.try_recv().is_ok());
+}
+
+#[test]
+fn test_timeout_from_duration() {
+    let runner = LoopRunner::new();
+    let start = mainloop::monotonic_time();
+    runner.run_after(|ml| {
+        let source = mainloop::timeout_source_from_duration(
+                Duration::from_millis(10));
+        let mlc = ml.clone();
+        source.set_callback(SourceCallback::once(move || {
+            mlc.quit();
+        }));
+        source.attach(ml.get_context());
+    });
+    assert!(start.elapsed() >= Duration::from_millis(10));
+}
+
+#[test]
+fn test_timeout_from_duration_seconds() {
+    let ctx = MainContext::new();
+    let source = mainloop::timeout_source_from_duration(
+            Duration::from_secs(3600));
+    let attached = source.attach(&ctx);
+    let ready = attached.as_source().ready_time().unwrap();
+    let now = mainloop::monotonic_time();
+    // Seconds timeouts may be rescheduled within a second's window
+    assert!(ready - now > Duration::from_secs(3598));
+}
+
+#[test]
+#[should_panic]
+fn test_timeout_from_duration_overflow() {
+    mainloop::timeout_source_from_duration(
+            Duration::from_millis(1 << 33) + Duration::from_millis(1));
+}
+
+#[test]
+fn test_monotonic_time() {
+    let t1 = mainloop::monotonic_time();
+    let t2 = t1 + Duration::from_millis(1500);
+    assert_eq!(t2.as_micros() - t1.as_micros(), 1_500_000);
+    assert_eq!(t2 - t1, Duration::from_millis(1500));
+    assert_eq!(t1 - t2, Duration::new(0, 0));
+    assert_eq!(t2 - Duration::from_millis(1500), t1);
+    assert!(t1 < t2);
+    assert!(mainloop::monotonic_time() >= t1);
+}
+
+#[test]
+fn test_real_time() {
+    let rt = mainloop::real_time();
+    let st = SystemTime::now();
+    let diff = match st.duration_since(rt) {
+        Ok(d) => d,
+        Err(e) => e.duration()
+    };
+    assert!(diff < Duration::from_secs(5));
+    assert!(rt > UNIX_EPOCH);
+}
@@ -0,0 +1,150 @@
+// This file is part of Grust, GObject introspection bindings for Rust
+//
+// Copyright (C) 2015  Mikhail Zabaluev <mikhail.zabaluev@gmail.com>
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; either
+// version 2.1 of the License, or (at your option) any later version.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+
+#[macro_use]
+extern crate grust;
+
+extern crate gobject_2_0_sys as gobject;
+
+use grust::gtype;
+use grust::gtype::GType;
+use grust::object;
+use grust::object::ObjectType;
+use grust::quark::Quark;
+use grust::refcount::Ref;
+use grust::wrap::Wrapper;
+
+use std::ptr;
+use std::sync::Arc;
+
+#[repr(C)]
+struct TestObject {
+    raw: gobject::GObject
+}
+
+unsafe impl Wrapper for TestObject {
+    type Raw = gobject::GObject;
+}
+
+unsafe impl ObjectType for TestObject {
+    fn get_type() -> GType {
+        gtype::OBJECT
+    }
+}
+
+fn new_object() -> Ref<TestObject> {
+    unsafe {
+        let raw = gobject::g_object_new(gtype::OBJECT.to_raw(), ptr::null());
+        Ref::from_raw(raw as *mut gobject::GObject)
+    }
+}
+
+fn test_quark() -> Quark {
+    g_static_quark!("grust-test-qdata")
+}
+
+#[test]
+fn test_set_qdata() {
+    let obj = new_object();
+    object::set_qdata(&*obj, test_quark(), String::from("data"));
+    let data = object::qdata::<_, String>(&*obj, test_quark());
+    assert_eq!(data.unwrap(), "data");
+}
+
+#[test]
+fn test_qdata_unset() {
+    let obj = new_object();
+    let data = object::qdata::<_, String>(&*obj, test_quark());
+    assert!(data.is_none());
+}
+
+#[test]
+fn test_qdata_type_mismatch() {
+    let obj = new_object();
+    object::set_qdata(&*obj, test_quark(), 42u32);
+    let data = object::qdata::<_, String>(&*obj, test_quark());
+    assert!(data.is_none());
+    let stolen = object::steal_qdata::<_, String>(&*obj, test_quark());
+    assert!(stolen.is_none());
+    // Data of another type is left in place
+    let data = object::qdata::<_, u32>(&*obj, test_quark());
+    assert_eq!(data, Some(42));
+}
+
+#[test]
+fn test_steal_qdata() {
+    let obj = new_object();
+    let arc = Arc::new(());
+    object::set_qdata(&*obj, test_quark(), arc.clone());
+    let stolen = object::steal_qdata::<_, Arc<()>>(&*obj, test_quark());
+    assert!(stolen.is_some());
+    assert_eq!(Arc::strong_count(&arc), 2);
+    let data = object::qdata::<_, Arc<()>>(&*obj, test_quark());
+    assert!(data.is_none());
+    drop(stolen);
+    assert_eq!(Arc::strong_count(&arc), 1);
+}
+
+#[test]
+fn test_remove_qdata() {
+    let obj = new_object();
+    let arc = Arc::new(());
+    object::set_qdata(&*obj, test_quark(), arc.clone());
+    assert_eq!(Arc::strong_count(&arc), 2);
+    object::remove_qdata(&*obj, test_quark());
+    assert_eq!(Arc::strong_count(&arc), 1);
+    let data = object::qdata::<_, Arc<()>>(&*obj, test_quark());
+    assert!(data.is_none());
+}
+
+#[test]
+fn test_qdata_replaced() {
+    let obj = new_object();
+    let arc = Arc::new(());
+    object::set_qdata(&*obj, test_quark(), arc.clone());
+    object::set_qdata(&*obj, test_quark(), String::from("other"));
+    assert_eq!(Arc::strong_count(&arc), 1);
+}
+
+#[test]
+fn test_qdata_dropped_with_object() {
+    let obj = new_object();
+    let arc = Arc::new(());
+    object::set_qdata(&*obj, test_quark(), arc.clone());
+    drop(obj);
+    assert_eq!(Arc::strong_count(&arc), 1);
+}
+
+#[test]
+fn test_qdata_foreign_pointer() {
+    let obj = new_object();
+    // A pointer set by other code under the same quark
+    let mut foreign = 0u8;
+    unsafe {
+        gobject::g_object_set_qdata(obj.as_mut_ptr(), test_quark().to_raw(),
+                                    &mut foreign as *mut u8 as *mut _);
+    }
+    assert!(object::qdata::<_, u32>(&*obj, test_quark()).is_none());
+    assert!(object::steal_qdata::<_, u32>(&*obj, test_quark()).is_none());
+    object::set_qdata(&*obj, test_quark(), 7u32);
+    assert_eq!(object::qdata::<_, u32>(&*obj, test_quark()), Some(7));
+    let p = unsafe {
+        gobject::g_object_get_qdata(obj.as_mut_ptr(), test_quark().to_raw())
+    };
+    assert!(p == &mut foreign as *mut u8 as *mut _);
+}
@@ -0,0 +1,103 @@
+// This file is part of Grust, GObject introspection bindings for Rust
+//
+// Copyright (C) 2015  Mikhail Zabaluev <mikhail.zabaluev@gmail.com>
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; either
+// version 2.1 of the License, or (at your option) any later version.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+
+// The panic policy is process-wide, so these tests are kept apart
+// from the other main loop tests and all use the same policy.
+
+extern crate grust;
+
+use grust::mainloop;
+use grust::mainloop::{LoopRunner, MainContext, MainLoop, SourceCallback};
+use grust::mainloop::CallbackResult::Continue;
+use grust::panic;
+use grust::panic::Policy;
+
+use std::panic as std_panic;
+use std::panic::AssertUnwindSafe;
+use std::sync::mpsc;
+
+fn panic_message(payload: Box<std::any::Any + Send>) -> String {
+    match payload.downcast::<&'static str>() {
+        Ok(s) => s.to_string(),
+        Err(payload) => *payload.downcast::<String>().unwrap()
+    }
+}
+
+#[test]
+fn test_resume_after_run() {
+    panic::set_policy(Policy::Resume);
+    assert_eq!(panic::policy(), Policy::Resume);
+    let res = std_panic::catch_unwind(AssertUnwindSafe(|| {
+        let runner = LoopRunner::new();
+        runner.run_after(|ml| {
+            let source = mainloop::idle_source_new();
+            source.set_callback(SourceCallback::new(|| -> mainloop::CallbackResult {
+                panic!("callback panicked");
+            }));
+            source.attach(ml.get_context());
+        });
+    }));
+    let payload = res.err().unwrap();
+    assert_eq!(panic_message(payload), "callback panicked");
+    assert!(!panic::is_pending());
+}
+
+#[test]
+fn test_resume_after_main_loop_run() {
+    panic::set_policy(Policy::Resume);
+    let (tx, rx) = mpsc::channel();
+    let ctx = MainContext::new();
+    let ml = MainLoop::new(Some(&ctx), false);
+    let res = std_panic::catch_unwind(AssertUnwindSafe(|| {
+        let source = mainloop::idle_source_new();
+        source.set_callback(SourceCallback::once(|| {
+            panic!("once panicked");
+        }));
+        source.attach(&ctx);
+        let source = mainloop::idle_source_new();
+        source.set_priority(mainloop::PRIORITY_LOW);
+        source.set_callback(SourceCallback::new(move || {
+            tx.send(()).unwrap();
+            Continue
+        }));
+        source.attach(&ctx);
+        ml.run();
+    }));
+    let payload = res.err().unwrap();
+    assert_eq!(panic_message(payload), "once panicked");
+    // The loop has been quit before the lower priority source was reached
+    assert!(rx.try_recv().is_err());
+}
+
+#[test]
+fn test_resume_after_iteration() {
+    panic::set_policy(Policy::Resume);
+    let ctx = MainContext::new();
+    let _acquired = ctx.acquire().unwrap();
+    let source = mainloop::idle_source_new();
+    source.set_callback(SourceCallback::once(|| {
+        panic!("iteration panicked");
+    }));
+    source.attach(&ctx);
+    let res = std_panic::catch_unwind(AssertUnwindSafe(|| {
+        ctx.iteration(false);
+    }));
+    let payload = res.err().unwrap();
+    assert_eq!(panic_message(payload), "iteration panicked");
+    assert!(!ctx.pending());
+}
@@ -0,0 +1,103 @@
+// This file is part of Grust, GObject introspection bindings for Rust
+//
+// Copyright (C) 2015  Mikhail Zabaluev <mikhail.zabaluev@gmail.com>
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; either
+// version 2.1 of the License, or (at your option) any later version.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+
+#[macro_use]
+extern crate grust;
+
+use grust::gstr::Utf8;
+use grust::quark;
+use grust::quark::{Quark, StaticQuark};
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+#[test]
+fn test_from_str() {
+    let q = Quark::from_str("grust-test-from-str").unwrap();
+    assert_eq!(q.to_bytes(), b"grust-test-from-str");
+    let q2: Quark = "grust-test-from-str".parse().unwrap();
+    assert_eq!(q, q2);
+    assert_eq!(q, Quark::from_static_str("grust-test-from-str\0"));
+}
+
+#[test]
+fn test_from_str_nul() {
+    assert!(Quark::from_str("grust\0test").is_err());
+}
+
+#[test]
+fn test_try_from_str() {
+    assert!(Quark::try_from_str("grust-test-never-interned").is_none());
+    // The lookup must not have interned the string
+    assert!(Quark::try_from_str("grust-test-never-interned").is_none());
+    let q = Quark::from_str("grust-test-try-from-str").unwrap();
+    assert_eq!(Quark::try_from_str("grust-test-try-from-str"), Some(q));
+    assert!(Quark::try_from_str("grust\0test").is_none());
+}
+
+#[test]
+fn test_hash_map_key() {
+    let mut map = HashMap::new();
+    map.insert(Quark::from_str("grust-test-key-a").unwrap(), 1);
+    map.insert(Quark::from_str("grust-test-key-b").unwrap(), 2);
+    assert_eq!(map[&Quark::from_str("grust-test-key-a").unwrap()], 1);
+    assert_eq!(map[&Quark::from_str("grust-test-key-b").unwrap()], 2);
+}
+
+#[test]
+fn test_ord() {
+    let a = Quark::from_str("grust-test-ord-first").unwrap();
+    let b = Quark::from_str("grust-test-ord-second").unwrap();
+    assert!(a < b);
+    assert_eq!(a.cmp(&a), std::cmp::Ordering::Equal);
+}
+
+#[test]
+fn test_intern_string() {
+    let s = String::from("grust-test-interned");
+    let interned = quark::intern_string(&s).unwrap();
+    assert_eq!(interned.to_str(), "grust-test-interned");
+    let again = quark::intern_string("grust-test-interned").unwrap();
+    assert_eq!(interned.as_ptr(), again.as_ptr());
+    assert!(quark::intern_string("grust\0test").is_err());
+}
+
+#[test]
+fn test_intern_static_string() {
+    let s = Utf8::from_static_str("grust-test-interned-static\0");
+    let interned = quark::intern_static_string(s);
+    assert_eq!(interned.to_str(), "grust-test-interned-static");
+    let again = quark::intern_string("grust-test-interned-static").unwrap();
+    assert_eq!(interned.as_ptr(), again.as_ptr());
+}
+
+#[test]
+fn test_static_quark_macro() {
+    let q = g_static_quark!("grust-test-static-quark");
+    assert_eq!(q.to_bytes(), b"grust-test-static-quark");
+    assert_eq!(q, Quark::from_str("grust-test-static-quark").unwrap());
+    assert_eq!(g_static_quark!("grust-test-static-quark"), q);
+}
+
+#[test]
+fn test_static_quark_const() {
+    static QUARK: StaticQuark = StaticQuark::new("grust-test-static-const\0");
+    let q = QUARK.get();
+    assert_eq!(q.to_bytes(), b"grust-test-static-const");
+    assert_eq!(QUARK.get(), q);
+}
@@ -0,0 +1,147 @@
+// This file is part of Grust, GObject introspection bindings for Rust
+//
+// Copyright (C) 2015  Mikhail Zabaluev <mikhail.zabaluev@gmail.com>
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; either
+// version 2.1 of the License, or (at your option) any later version.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+
+extern crate grust;
+
+use grust::mainloop;
+use grust::mainloop::{LoopRunner, MainContext, SourceCallback};
+use grust::mainloop::CallbackResult::{Continue, Remove};
+use grust::testclock::TestClock;
+
+use std::sync::mpsc;
+use std::time::Duration;
+
+#[test]
+fn test_advance() {
+    let ctx = MainContext::new();
+    let _acquired = ctx.acquire().unwrap();
+    let clock = TestClock::new(&ctx);
+    let (tx, rx) = mpsc::channel();
+    let source = clock.timeout_source_new(Duration::from_secs(60));
+    source.set_callback(SourceCallback::once(move || {
+        tx.send(()).unwrap();
+    }));
+    source.attach(&ctx);
+    assert!(!ctx.iteration(false));
+    clock.advance(Duration::from_secs(59));
+    assert!(!ctx.iteration(false));
+    assert!(rx.try_recv().is_err());
+    clock.advance(Duration::from_secs(1));
+    assert!(ctx.iteration(false));
+    assert!(rx.try_recv().is_ok());
+}
+
+#[test]
+fn test_installed_timeout() {
+    let ctx = MainContext::new();
+    let _acquired = ctx.acquire().unwrap();
+    let clock = TestClock::new(&ctx);
+    let start = clock.now();
+    let (tx, rx) = mpsc::channel();
+    let mut count = 0;
+    let source = mainloop::timeout_source_new(100);
+    source.set_callback(SourceCallback::new(move || {
+        count += 1;
+        tx.send(count).unwrap();
+        if count < 3 { Continue } else { Remove }
+    }));
+    source.attach(&ctx);
+    for i in 1..4 {
+        clock.advance(Duration::from_millis(100));
+        assert!(ctx.iteration(false));
+        assert_eq!(rx.try_recv().unwrap(), i);
+    }
+    assert_eq!(clock.now() - start, Duration::from_millis(300));
+    clock.advance(Duration::from_millis(100));
+    assert!(!ctx.iteration(false));
+}
+
+#[test]
+fn test_uninstalled() {
+    let ctx = MainContext::new();
+    {
+        let _clock = TestClock::new(&ctx);
+    }
+    let source = mainloop::timeout_source_new(100);
+    let attached = source.attach(&ctx);
+    // A real timeout source has its ready time set on the real clock
+    assert!(attached.as_source().ready_time().is_some());
+}
+
+#[test]
+fn test_auto_advance_with_runner() {
+    let runner = LoopRunner::new();
+    let clock = TestClock::new(runner.get_context());
+    clock.set_auto_advance(true);
+    let start = clock.now();
+    let real_start = mainloop::monotonic_time();
+    runner.run_after(|ml| {
+        let source = mainloop::timeout_source_from_duration(
+                Duration::from_secs(3600));
+        let mlc = ml.clone();
+        source.set_callback(SourceCallback::once(move || {
+            mlc.quit();
+        }));
+        source.attach(ml.get_context());
+    });
+    assert_eq!(clock.now() - start, Duration::from_secs(3600));
+    assert!(real_start.elapsed() < Duration::from_secs(60));
+}
+
+#[test]
+fn test_nested_clocks() {
+    let runner = LoopRunner::new();
+    let outer = TestClock::new(runner.get_context());
+    outer.set_auto_advance(true);
+    let start = outer.now();
+    {
+        let _inner = TestClock::new(runner.get_context());
+    }
+    // The outer clock's poll function is back in effect
+    let real_start = mainloop::monotonic_time();
+    runner.run_after(|ml| {
+        let source = outer.timeout_source_new(Duration::from_secs(3600));
+        let mlc = ml.clone();
+        source.set_callback(SourceCallback::once(move || {
+            mlc.quit();
+        }));
+        source.attach(ml.get_context());
+    });
+    assert_eq!(outer.now() - start, Duration::from_secs(3600));
+    assert!(real_start.elapsed() < Duration::from_secs(60));
+}
+
+#[test]
+fn test_other_context_real_time() {
+    let ctx = MainContext::new();
+    let _clock = TestClock::new(&ctx);
+    let other = MainContext::new();
+    let _acquired = other.acquire().unwrap();
+    let (tx, rx) = mpsc::channel();
+    // Created while the clock is installed, but attached elsewhere
+    let source = mainloop::timeout_source_new(10);
+    source.set_callback(SourceCallback::once(move || {
+        tx.send(()).unwrap();
+    }));
+    source.attach(&other);
+    let start = mainloop::monotonic_time();
+    while rx.try_recv().is_err() {
+        other.iteration(true);
+    }
+    assert!(start.elapsed() >= Duration::from_millis(10));
+}
@@ -0,0 +1,138 @@
+// This file is part of Grust, GObject introspection bindings for Rust
+//
+// Copyright (C) 2015  Mikhail Zabaluev <mikhail.zabaluev@gmail.com>
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; either
+// version 2.1 of the License, or (at your option) any later version.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+
+extern crate grust;
+
+use grust::mainloop::{LoopRunner, MainContext};
+use grust::threadpool::{Job, ThreadPool};
+
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc;
+use std::thread;
+
+#[test]
+fn test_push() {
+    let (tx, rx) = mpsc::channel();
+    let tx = Mutex::new(tx);
+    let pool = ThreadPool::new(move |n: u32| {
+        tx.lock().unwrap().send(n * 2).unwrap();
+    }, Some(2), false).unwrap();
+    assert_eq!(pool.max_threads(), Some(2));
+    for i in 0..10 {
+        pool.push(i).unwrap();
+    }
+    pool.shutdown();
+    let mut results: Vec<u32> = rx.iter().collect();
+    results.sort();
+    let expected: Vec<u32> = (0..10).map(|n| n * 2).collect();
+    assert_eq!(results, expected);
+}
+
+#[test]
+fn test_exclusive() {
+    let counter = Arc::new(Mutex::new(0));
+    let cc = counter.clone();
+    let pool = ThreadPool::new(move |n: u32| {
+        *cc.lock().unwrap() += n;
+    }, Some(3), true).unwrap();
+    assert_eq!(pool.num_threads(), 3);
+    for i in 1..5 {
+        pool.push(i).unwrap();
+    }
+    drop(pool);
+    assert_eq!(*counter.lock().unwrap(), 10);
+}
+
+#[test]
+#[should_panic(expected = "needs a limit on threads")]
+fn test_exclusive_unlimited() {
+    let _ = ThreadPool::for_jobs(None, true);
+}
+
+#[test]
+#[should_panic(expected = "needs a limit on threads")]
+fn test_exclusive_set_unlimited() {
+    let pool = ThreadPool::for_jobs(Some(1), true).unwrap();
+    let _ = pool.set_max_threads(None);
+}
+
+#[test]
+fn test_set_max_threads() {
+    let pool = ThreadPool::for_jobs(None, false).unwrap();
+    assert_eq!(pool.max_threads(), None);
+    pool.set_max_threads(Some(4)).unwrap();
+    assert_eq!(pool.max_threads(), Some(4));
+    assert_eq!(pool.unprocessed(), 0);
+}
+
+#[test]
+fn test_jobs() {
+    let (tx, rx) = mpsc::channel();
+    let pool = ThreadPool::for_jobs(Some(2), false).unwrap();
+    for i in 0..4 {
+        let tx = tx.clone();
+        pool.spawn(move || {
+            tx.send(i).unwrap();
+        }).unwrap();
+    }
+    pool.push(Job::new(move || {
+        tx.send(4).unwrap();
+    })).unwrap();
+    pool.shutdown();
+    let mut results: Vec<i32> = rx.iter().collect();
+    results.sort();
+    assert_eq!(results, vec![0, 1, 2, 3, 4]);
+}
+
+#[test]
+fn test_run_and_report() {
+    let pool = ThreadPool::for_jobs(Some(2), false).unwrap();
+    let runner = LoopRunner::new();
+    let loop_thread = thread::current().name().map(|s| s.to_string());
+    let res = runner.run_until(|quitter| {
+        let qc = quitter.clone();
+        pool.run_and_report(quitter.get_context(), || {
+            thread::current().name().map(|s| s.to_string())
+        }, move |worker_thread| {
+            let this_thread = thread::current().name().map(|s| s.to_string());
+            qc.quit((worker_thread, this_thread));
+        }).unwrap();
+    });
+    let (worker_thread, report_thread) = res;
+    assert!(worker_thread != loop_thread);
+    assert_eq!(report_thread, loop_thread);
+}
+
+#[test]
+fn test_run_and_report_unowned_context() {
+    let pool = ThreadPool::for_jobs(Some(2), false).unwrap();
+    let ctx = MainContext::new();
+    let (tx, rx) = mpsc::channel();
+    let this_thread = thread::current().id();
+    pool.run_and_report(&ctx, || 42, move |res| {
+        tx.send((res, thread::current().id())).unwrap();
+    }).unwrap();
+    // Wait for the work to be done while nobody owns the context
+    pool.shutdown();
+    assert!(rx.try_recv().is_err());
+    let _acquired = ctx.acquire().unwrap();
+    while !ctx.iteration(true) { }
+    let (res, report_thread) = rx.try_recv().unwrap();
+    assert_eq!(res, 42);
+    assert_eq!(report_thread, this_thread);
+}